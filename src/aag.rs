@@ -1,13 +1,17 @@
 use crate::aig;
 use nom::{
     bytes::complete::tag,
-    character::complete::{newline, space1, u64},
+    character::complete::{char, newline, not_line_ending, space1, u64},
     combinator::{flat_map, map},
     multi::count,
-    sequence::{delimited, preceded, terminated, tuple},
+    sequence::{delimited, preceded, separated_pair, terminated, tuple},
     Finish, IResult,
 };
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    rc::Rc,
+};
 
 struct Header {
     i: u64,
@@ -61,7 +65,43 @@ fn and(input: &[u8]) -> IResult<&[u8], And> {
     )(input)
 }
 
-pub fn parse(input: &[u8]) -> Result<(usize, Vec<Box<aig::AIG>>), nom::error::Error<&[u8]>> {
+pub type Outputs = Vec<Box<aig::AIG>>;
+
+#[derive(Debug)]
+pub enum Error<'a> {
+    /// malformed AIGER text
+    Syntax(nom::error::Error<&'a [u8]>),
+    /// variable used as a fan-in but never defined
+    Undefined(usize),
+    /// variable of an AND gate that depends on itself
+    Cycle(usize),
+}
+
+impl<'a> From<nom::error::Error<&'a [u8]>> for Error<'a> {
+    fn from(value: nom::error::Error<&'a [u8]>) -> Self {
+        Self::Syntax(value)
+    }
+}
+
+impl Display for Error<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Syntax(e) => write!(f, "syntax error: {:?}", e.code),
+            Error::Undefined(var) => write!(f, "literal {} is undefined", var * 2),
+            Error::Cycle(var) => write!(f, "AND gate {} is on a combinational cycle", var * 2),
+        }
+    }
+}
+
+fn lit(graph: &HashMap<usize, Rc<aig::AIG>>, lit: &Lit) -> Result<Rc<aig::AIG>, Error<'static>> {
+    let node = graph
+        .get(&lit.var)
+        .ok_or(Error::Undefined(lit.var))?
+        .clone();
+    Ok(if lit.neg { aig::AIG::inv(node) } else { node })
+}
+
+pub fn parse(input: &[u8]) -> Result<(usize, Outputs), Error<'_>> {
     let ast = flat_map(terminated(header, newline), |h| {
         assert_eq!(h.l, 0);
         tuple((
@@ -73,29 +113,124 @@ pub fn parse(input: &[u8]) -> Result<(usize, Vec<Box<aig::AIG>>), nom::error::Er
     .finish()?
     .1;
 
-    let mut graph: HashMap<usize, Box<aig::AIG>> = ast
+    // variable 0 is the constant, literal 0 is false and literal 1 is true
+    let mut graph: HashMap<usize, Rc<aig::AIG>> = ast
         .0
         .iter()
         .enumerate()
-        .map(|(i, v)| (v.var, i.into()))
+        .map(|(i, v)| (v.var, Box::<aig::AIG>::from(i).into()))
+        .chain([(0, Box::<aig::AIG>::from(false).into())])
         .collect();
 
-    let mut queue: VecDeque<And> = ast.2.into();
+    let gates: HashMap<usize, &And> = ast.2.iter().map(|g| (g.lhs.var, g)).collect();
 
-    while !queue.is_empty() {
-        let cur = queue.pop_back().unwrap();
-        if let (Some(rhs0), Some(rhs1)) = (graph.get(&cur.rhs0.var), graph.get(&cur.rhs1.var)) {
-            graph.insert(cur.lhs.var, rhs0.neg(cur.rhs0.neg) & rhs1.neg(cur.rhs1.neg));
-        } else {
-            queue.push_front(cur);
+    // resolve AND gates in topological order with an explicit depth first search,
+    // a gate is expanded once and built once all of its fan-ins are in the graph
+    let mut visiting = HashSet::new();
+    let mut stack = vec![];
+    for gate in &ast.2 {
+        stack.push(gate.lhs.var);
+        while let Some(&var) = stack.last() {
+            if graph.contains_key(&var) {
+                stack.pop();
+                continue;
+            }
+            let cur = gates[&var];
+            if visiting.insert(var) {
+                for fanin in [cur.rhs0.var, cur.rhs1.var] {
+                    if graph.contains_key(&fanin) {
+                        continue;
+                    }
+                    if visiting.contains(&fanin) {
+                        return Err(Error::Cycle(fanin));
+                    }
+                    if !gates.contains_key(&fanin) {
+                        return Err(Error::Undefined(fanin));
+                    }
+                    stack.push(fanin);
+                }
+            } else {
+                visiting.remove(&var);
+                stack.pop();
+                let node = aig::AIG::and(lit(&graph, &cur.rhs0)?, lit(&graph, &cur.rhs1)?);
+                graph.insert(var, node);
+            }
         }
     }
 
     let outputs = ast
         .1
         .iter()
-        .map(|v| graph.get(&v.var).unwrap().neg(v.neg))
-        .collect();
+        .map(|v| lit(&graph, v).map(|node| Box::new(Rc::unwrap_or_clone(node))))
+        .collect::<Result<_, _>>()?;
 
     Ok((ast.0.len(), outputs))
 }
+
+/// Input names of the symbol table, as `(input, name)`
+pub fn names(input: &[u8]) -> Vec<(usize, String)> {
+    let symbol = |text| -> IResult<&[u8], (u64, &[u8])> {
+        preceded(char('i'), separated_pair(u64, space1, not_line_ending))(text)
+    };
+    input
+        .split(|c| *c == b'\n')
+        .take_while(|text| *text != b"c")
+        .filter_map(|text| symbol(text).ok())
+        .map(|(_, (i, name))| (i as usize, String::from_utf8_lossy(name).into_owned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn constants() {
+        // o0 = 0, o1 = 1, o2 = i0 & 1, o3 = !(i1 & 0)
+        let (inputs, outputs) =
+            super::parse(b"aag 4 2 0 4 2\n2\n4\n0\n1\n6\n9\n6 2 1\n8 4 0\n").unwrap();
+        assert_eq!(inputs, 2);
+        assert_eq!(
+            outputs.iter().map(|o| o.to_string()).collect::<Vec<_>>(),
+            vec!["0", "1", "i0", "1"]
+        );
+        for term in 0..4 {
+            let input = [(term & 1) == 1, (term & 2) == 2];
+            assert!(!outputs[0].eval(&input));
+            assert!(outputs[1].eval(&input));
+            assert_eq!(outputs[2].eval(&input), input[0]);
+            assert!(outputs[3].eval(&input));
+        }
+    }
+
+    #[test]
+    fn names() {
+        let aag = b"aag 1 1 0 1 0\n2\n2\ni0 in[0]\no0 out\nc\ni1 comment\n";
+        assert_eq!(super::names(aag), [(0, "in[0]".to_string())]);
+    }
+
+    #[test]
+    fn order() {
+        // o0 = (i0 & i1) & i2 with the gates listed in reverse order
+        let (_, outputs) = super::parse(b"aag 5 3 0 1 2\n2\n4\n6\n10\n10 8 6\n8 2 4\n").unwrap();
+        assert_eq!(outputs[0].to_string(), "((i0 & i1) & i2)");
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(matches!(
+            super::parse(b"aag 3 1 0 1 2\n2\n6\n6 4 2\n4 6 2\n"),
+            Err(super::Error::Cycle(_))
+        ));
+        assert!(matches!(
+            super::parse(b"aag 3 1 0 1 1\n2\n4\n4 6 2\n"),
+            Err(super::Error::Undefined(3))
+        ));
+        assert!(matches!(
+            super::parse(b"aag 2 1 0 1 0\n2\n4\n"),
+            Err(super::Error::Undefined(2))
+        ));
+        assert!(matches!(
+            super::parse(b"aag 1 1 0 1\n2\n"),
+            Err(super::Error::Syntax(_))
+        ));
+    }
+}
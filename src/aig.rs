@@ -1,27 +1,89 @@
-use std::fmt::Display;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    fmt::Display,
+    rc::Rc,
+};
 
 #[derive(Debug, Clone)]
 pub struct Sym(pub usize);
 
 #[derive(Debug, Clone)]
-pub struct Inv(pub Box<AIG>);
+pub struct Inv(pub Rc<AIG>);
 
 #[derive(Debug, Clone)]
-pub struct And(pub Box<AIG>, pub Box<AIG>);
+pub struct And(pub Rc<AIG>, pub Rc<AIG>);
+
+#[derive(Debug, Clone)]
+pub struct Const(pub bool);
 
 #[derive(Debug, Clone)]
 pub enum AIG {
     A(And),
     I(Inv),
     G(Sym),
+    C(Const),
 }
 
-impl Display for Box<AIG> {
+impl Display for AIG {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self.clone() {
-            AIG::A(And(l, r)) => write!(f, "({} & {})", l, r),
-            AIG::I(Inv(r)) => write!(f, "!{}", r),
-            AIG::G(Sym(i)) => write!(f, "i{}", i),
+        // nodes still to print and the text between them, last one first
+        enum Item<'a> {
+            Node(&'a AIG),
+            Text(&'static str),
+        }
+        let mut stack = vec![Item::Node(self)];
+        while let Some(item) = stack.pop() {
+            match item {
+                Item::Text(t) => write!(f, "{}", t)?,
+                Item::Node(AIG::A(And(l, r))) => {
+                    write!(f, "(")?;
+                    stack.extend([
+                        Item::Text(")"),
+                        Item::Node(r),
+                        Item::Text(" & "),
+                        Item::Node(l),
+                    ]);
+                }
+                Item::Node(AIG::I(Inv(r))) => {
+                    write!(f, "!")?;
+                    stack.push(Item::Node(r));
+                }
+                Item::Node(AIG::G(Sym(i))) => write!(f, "i{}", i)?,
+                Item::Node(AIG::C(Const(v))) => write!(f, "{}", *v as u8)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AIG {
+    // nodes only held by this one are dropped from a stack, not recursively
+    fn drop(&mut self) {
+        thread_local! {
+            static LEAF: Rc<AIG> = Rc::new(AIG::C(Const(false)));
+        }
+        fn take(aig: &mut AIG, stack: &mut Vec<Rc<AIG>>) {
+            let mut detach =
+                |r: &mut Rc<AIG>| stack.push(std::mem::replace(r, LEAF.with(Rc::clone)));
+            match aig {
+                AIG::A(And(l, r)) => {
+                    detach(l);
+                    detach(r);
+                }
+                AIG::I(Inv(r)) => detach(r),
+                AIG::G(_) | AIG::C(_) => (),
+            }
+        }
+        if matches!(self, AIG::G(_) | AIG::C(_)) {
+            return;
+        }
+        let mut stack = vec![];
+        take(self, &mut stack);
+        while let Some(rc) = stack.pop() {
+            if let Ok(mut node) = Rc::try_unwrap(rc) {
+                take(&mut node, &mut stack);
+            }
         }
     }
 }
@@ -32,40 +94,399 @@ impl From<usize> for Box<AIG> {
     }
 }
 
+impl From<bool> for Box<AIG> {
+    fn from(value: bool) -> Self {
+        Box::new(AIG::C(Const(value)))
+    }
+}
+
 impl std::ops::BitAnd for Box<AIG> {
     type Output = Self;
     fn bitand(self, rhs: Self) -> Self::Output {
-        Box::new(AIG::A(And(self, rhs)))
+        Box::new(Rc::unwrap_or_clone(AIG::and(self.into(), rhs.into())))
+    }
+}
+
+impl std::ops::BitOr for Box<AIG> {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self::Output {
+        !(!self & !rhs)
+    }
+}
+
+impl std::ops::BitXor for Box<AIG> {
+    type Output = Self;
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        (self.clone() & !rhs.clone()) | (!self & rhs)
     }
 }
 
 impl std::ops::Not for Box<AIG> {
     type Output = Self;
     fn not(self) -> Self::Output {
-        Box::new(AIG::I(Inv(self)))
+        Box::new(Rc::unwrap_or_clone(AIG::inv(self.into())))
     }
 }
 
 impl AIG {
+    /// `l & r` over shared nodes
+    pub fn and(l: Rc<Self>, r: Rc<Self>) -> Rc<Self> {
+        match (&*l, &*r) {
+            // x & 0 = 0, x & 1 = x
+            (AIG::C(Const(false)), _) | (_, AIG::C(Const(true))) => l,
+            (_, AIG::C(Const(false))) | (AIG::C(Const(true)), _) => r,
+            _ => Rc::new(AIG::A(And(l, r))),
+        }
+    }
+    /// `!r` over shared nodes
+    pub fn inv(r: Rc<Self>) -> Rc<Self> {
+        match &*r {
+            // !!x = x
+            AIG::I(Inv(x)) => x.clone(),
+            AIG::C(Const(v)) => Rc::new(AIG::C(Const(!v))),
+            _ => Rc::new(AIG::I(Inv(r))),
+        }
+    }
     pub fn neg(&self, neg: bool) -> Box<Self> {
         if neg {
-            Box::new(AIG::I(Inv(Box::new(self.clone()))))
+            !Box::new(self.clone())
         } else {
             Box::new(self.clone())
         }
     }
+    /// value of the node, shared nodes are evaluated once
     pub fn eval(&self, syms: &[bool]) -> bool {
-        match self {
-            AIG::A(And(l, r)) => l.eval(syms) & r.eval(syms),
-            AIG::I(Inv(r)) => !r.eval(syms),
-            AIG::G(Sym(i)) => syms[*i],
+        let mut values: HashMap<*const AIG, bool> = HashMap::new();
+        for node in topological([self]) {
+            let v = match node {
+                AIG::A(And(l, r)) => values[&ptr(l)] & values[&ptr(r)],
+                AIG::I(Inv(r)) => !values[&ptr(r)],
+                AIG::G(Sym(i)) => syms[*i],
+                AIG::C(Const(v)) => *v,
+            };
+            values.insert(ptr(node), v);
         }
+        values[&ptr(self)]
     }
     pub fn syms(&self) -> usize {
-        match self {
-            AIG::A(And(l, r)) => std::cmp::max(l.syms(), r.syms()),
-            AIG::I(Inv(r)) => r.syms(),
-            AIG::G(Sym(i)) => *i + 1,
+        self.support().last().map_or(0, |i| i + 1)
+    }
+
+    /// inputs in the cone of the node, sorted
+    pub fn support(&self) -> Vec<usize> {
+        let mut seen = HashSet::new();
+        let mut support = vec![];
+        let mut stack = vec![self];
+        while let Some(aig) = stack.pop() {
+            if !seen.insert(ptr(aig)) {
+                continue;
+            }
+            if let AIG::G(Sym(i)) = aig {
+                support.push(*i);
+            }
+            stack.extend(children(aig));
         }
+        support.sort();
+        support.dedup();
+        support
+    }
+}
+
+/// Single output that is true when any pair of outputs differs
+pub fn miter(a: &[Box<AIG>], b: &[Box<AIG>]) -> Box<AIG> {
+    assert_eq!(a.len(), b.len());
+    a.iter()
+        .zip(b)
+        .fold(false.into(), |acc, (a, b)| acc | (a.clone() ^ b.clone()))
+}
+
+fn ptr(aig: &AIG) -> *const AIG {
+    aig as *const AIG
+}
+
+/// Distinct nodes reachable from `roots`, every node after its children and
+/// left children first, without recursion so that deep chains fit the stack
+pub fn topological<'a>(roots: impl IntoIterator<Item = &'a AIG>) -> Vec<&'a AIG> {
+    let mut order = vec![];
+    let mut seen = HashSet::new();
+    let mut stack: Vec<(&AIG, bool)> = roots.into_iter().map(|r| (r, false)).collect();
+    stack.reverse();
+    while let Some((node, done)) = stack.pop() {
+        if done {
+            order.push(node);
+        } else if seen.insert(ptr(node)) {
+            stack.push((node, true));
+            match node {
+                AIG::A(And(l, r)) => stack.extend([(&**r, false), (&**l, false)]),
+                AIG::I(Inv(r)) => stack.push((r, false)),
+                AIG::G(_) | AIG::C(_) => (),
+            }
+        }
+    }
+    order
+}
+
+fn children(aig: &AIG) -> Vec<&AIG> {
+    match aig {
+        AIG::A(And(l, r)) => vec![l, r],
+        AIG::I(Inv(r)) => vec![r],
+        AIG::G(_) | AIG::C(_) => vec![],
+    }
+}
+
+/// Number of references to every node reachable from `outputs`
+fn fanouts(outputs: &[Box<AIG>]) -> HashMap<*const AIG, usize> {
+    let mut refs = HashMap::new();
+    let mut stack = vec![];
+    for output in outputs {
+        refs.insert(ptr(output), 1);
+        stack.push(&**output);
+    }
+    while let Some(node) = stack.pop() {
+        for child in children(node) {
+            let count = refs.entry(ptr(child)).or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                stack.push(child);
+            }
+        }
+    }
+    refs
+}
+
+/// Number of distinct AND nodes reachable from `outputs`
+pub fn ands(outputs: &[Box<AIG>]) -> usize {
+    let mut stack: Vec<&AIG> = outputs.iter().map(|o| &**o).collect();
+    let mut seen = HashSet::new();
+    let mut count = 0;
+    while let Some(node) = stack.pop() {
+        if !seen.insert(ptr(node)) {
+            continue;
+        }
+        count += matches!(node, AIG::A(_)) as usize;
+        stack.extend(children(node));
+    }
+    count
+}
+
+/// Number of AND nodes on the longest path from an input to an output
+pub fn depth(outputs: &[Box<AIG>]) -> usize {
+    let mut depths: HashMap<*const AIG, usize> = HashMap::new();
+    for node in topological(outputs.iter().map(|o| &**o)) {
+        let d = match node {
+            AIG::A(And(l, r)) => std::cmp::max(depths[&ptr(l)], depths[&ptr(r)]) + 1,
+            AIG::I(Inv(r)) => depths[&ptr(r)],
+            AIG::G(_) | AIG::C(_) => 0,
+        };
+        depths.insert(ptr(node), d);
+    }
+    outputs.iter().map(|o| depths[&ptr(o)]).max().unwrap_or(0)
+}
+
+/// Leaves of the AND super-gate rooted at `aig`, the tree of AND nodes
+/// reached without crossing an inverter or a node with other fanouts
+fn super_gate<'a>(aig: &'a AIG, refs: &HashMap<*const AIG, usize>) -> Vec<&'a AIG> {
+    let mut leaves = vec![];
+    let mut stack = vec![(aig, true)];
+    while let Some((node, root)) = stack.pop() {
+        match node {
+            AIG::A(And(l, r)) if root || refs[&ptr(node)] == 1 => {
+                stack.extend([(&**r, false), (&**l, false)]);
+            }
+            _ => leaves.push(node),
+        }
+    }
+    leaves
+}
+
+/// Identity of a super-gate leaf as (base, inverted), inputs are compared by
+/// index since the same input may be held by different nodes
+fn leaf(aig: &AIG) -> ((usize, *const AIG), bool) {
+    let base = |aig: &AIG| match aig {
+        AIG::G(Sym(i)) => (*i, std::ptr::null()),
+        _ => (usize::MAX, ptr(aig)),
+    };
+    match aig {
+        AIG::I(Inv(r)) => (base(r), true),
+        _ => (base(aig), false),
+    }
+}
+
+/// Operands of the super-gate rooted at `aig` by leaf identity, `None` when
+/// two of them are complementary
+fn operands<'a>(aig: &'a AIG, refs: &HashMap<*const AIG, usize>) -> Option<Vec<&'a AIG>> {
+    let mut leaves = super_gate(aig, refs);
+    leaves.sort_by_key(|l| leaf(l));
+    leaves.dedup_by_key(|l| leaf(l));
+    let contradiction = leaves.windows(2).any(|w| leaf(w[0]).0 == leaf(w[1]).0);
+    (!contradiction).then_some(leaves)
+}
+
+/// Pair the two shallowest of the balanced `nodes` until a single tree is left
+fn pair(mut nodes: Vec<(Rc<AIG>, usize)>) -> (Rc<AIG>, usize) {
+    let mut heap: BinaryHeap<_> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, (_, depth))| (Reverse(*depth), Reverse(i)))
+        .collect();
+    while heap.len() > 1 {
+        let (Reverse(dl), Reverse(l)) = heap.pop().unwrap();
+        let (Reverse(dr), Reverse(r)) = heap.pop().unwrap();
+        let (l, r) = (nodes[l].0.clone(), nodes[r].0.clone());
+        let node = AIG::and(l.clone(), r.clone());
+        let depth = if Rc::ptr_eq(&node, &l) {
+            dl
+        } else if Rc::ptr_eq(&node, &r) {
+            dr
+        } else if let AIG::C(_) = *node {
+            0
+        } else {
+            std::cmp::max(dl, dr) + 1
+        };
+        heap.push((Reverse(depth), Reverse(nodes.len())));
+        nodes.push((node, depth));
+    }
+    let (_, Reverse(root)) = heap.pop().unwrap();
+    nodes.swap_remove(root)
+}
+
+/// Rebuild every AND super-gate as a tree of minimal depth
+pub fn balance(outputs: &[Box<AIG>]) -> Vec<Box<AIG>> {
+    let refs = fanouts(outputs);
+    // balanced node and depth of every super-gate root and leaf, computed
+    // once the balanced operands are known
+    let mut memo: HashMap<*const AIG, (Rc<AIG>, usize)> = HashMap::new();
+    let mut stack: Vec<(&AIG, bool)> = outputs.iter().rev().map(|o| (&**o, false)).collect();
+    while let Some((aig, done)) = stack.pop() {
+        if memo.contains_key(&ptr(aig)) {
+            continue;
+        }
+        if !done {
+            stack.push((aig, true));
+            match aig {
+                AIG::A(_) => {
+                    let leaves = operands(aig, &refs).unwrap_or_default();
+                    stack.extend(leaves.into_iter().rev().map(|l| (l, false)));
+                }
+                AIG::I(Inv(r)) => stack.push((r, false)),
+                AIG::G(_) | AIG::C(_) => (),
+            }
+            continue;
+        }
+        let result = match aig {
+            AIG::A(_) => match operands(aig, &refs) {
+                Some(leaves) => pair(leaves.iter().map(|l| memo[&ptr(l)].clone()).collect()),
+                // x & !x = 0
+                None => (Rc::new(AIG::C(Const(false))), 0),
+            },
+            AIG::I(Inv(r)) => {
+                let (node, depth) = memo[&ptr(r)].clone();
+                (AIG::inv(node), depth)
+            }
+            AIG::G(_) | AIG::C(_) => (Rc::new(aig.clone()), 0),
+        };
+        memo.insert(ptr(aig), result);
+    }
+    outputs
+        .iter()
+        .map(|o| Box::new(Rc::unwrap_or_clone(memo[&ptr(o)].0.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ands, balance, depth, miter, topological, Const, AIG};
+
+    #[test]
+    fn simplify() {
+        let x: Box<AIG> = 0.into();
+        let f: Box<AIG> = false.into();
+        let t: Box<AIG> = true.into();
+        assert!(matches!(*(x.clone() & f.clone()), AIG::C(Const(false))));
+        assert!(matches!(*(f.clone() & x.clone()), AIG::C(Const(false))));
+        assert_eq!((x.clone() & t.clone()).to_string(), "i0");
+        assert_eq!((t.clone() & x.clone()).to_string(), "i0");
+        assert_eq!((!!x.clone()).to_string(), "i0");
+        assert_eq!((!x.clone()).to_string(), "!i0");
+        assert!(matches!(*!t, AIG::C(Const(false))));
+        assert!(matches!(*!f, AIG::C(Const(true))));
+        assert_eq!(x.neg(true).neg(true).to_string(), "i0");
+    }
+
+    #[test]
+    fn ops() {
+        let a: Box<AIG> = 0.into();
+        let b: Box<AIG> = 1.into();
+        let or = a.clone() | b.clone();
+        let xor = a.clone() ^ b.clone();
+        let m = miter(&[or.clone(), a.clone()], &[xor.clone(), !!a.clone()]);
+        for term in 0..4 {
+            let input = [(term & 1) == 1, (term & 2) == 2];
+            assert_eq!(or.eval(&input), input[0] | input[1]);
+            assert_eq!(xor.eval(&input), input[0] ^ input[1]);
+            assert_eq!(m.eval(&input), input[0] & input[1]);
+        }
+        assert_eq!(m.support(), [0, 1]);
+        assert_eq!((!b).syms(), 2);
+        assert_eq!(Box::<AIG>::from(true).support(), []);
+    }
+
+    #[test]
+    fn balanced() {
+        // ((((((i0 & i1) & i2) & !i3) & i4) & i5) & i6) & i7
+        let chain = (1..8).fold(Box::<AIG>::from(0), |acc, i| {
+            acc & if i == 3 {
+                !Box::<AIG>::from(i)
+            } else {
+                i.into()
+            }
+        });
+        let inverted = !chain.clone() & Box::<AIG>::from(8);
+        for (outputs, before, after) in [(vec![chain], 7, 3), (vec![inverted], 8, 4)] {
+            assert_eq!(depth(&outputs), before);
+            let balanced = balance(&outputs);
+            assert_eq!(depth(&balanced), after);
+            assert_eq!(ands(&balanced), ands(&outputs));
+            for term in 0..512 {
+                let input: Vec<_> = (0..9).map(|i| (term >> i) & 1 == 1).collect();
+                assert_eq!(outputs[0].eval(&input), balanced[0].eval(&input));
+            }
+        }
+
+        // x & y & !x collapses to 0
+        let x: Box<AIG> = 0.into();
+        let y: Box<AIG> = 1.into();
+        let f = balance(&[(x.clone() & y) & !x]);
+        assert!(matches!(*f[0], AIG::C(Const(false))));
+    }
+
+    #[test]
+    fn deep() {
+        // a chain of ANDs alternating between two inputs, far deeper than the
+        // stack of a test thread would allow a recursive walker to go
+        let n = 100_000;
+        let chain = (0..n).fold(Box::<AIG>::from(1), |acc, i| acc & Box::<AIG>::from(i % 2));
+        let outputs = vec![!chain];
+        assert_eq!(topological([&*outputs[0]]).len(), 2 * n + 2);
+        assert_eq!(depth(&outputs), n);
+        for term in 0..4 {
+            let input = [(term & 1) == 1, (term & 2) == 2];
+            assert_eq!(outputs[0].eval(&input), term != 3);
+        }
+        assert_eq!(outputs[0].to_string().matches('&').count(), n);
+        assert_eq!(depth(&balance(&outputs)), 1);
+
+        let g = crate::opt::Graph::from_aig(2, &outputs);
+        assert_eq!((g.ands(), g.depth()), (n, n));
+        assert_eq!(crate::opt::Graph::from_aig(2, &g.to_aig()).ands(), n);
+        let mut m = crate::bdd::Manager::new(2);
+        let f = m.from_aig(&outputs[0]);
+        assert_eq!(m.sat_count(f), 3);
+        assert_eq!(crate::bdd::dfs_order(&outputs, 2), [1, 0]);
+        let mut cnf = crate::sat::Cnf::new(2);
+        let lits = cnf.encode(&outputs);
+        let mut solver = crate::sat::Solver::from(&cnf);
+        assert!(solver.solve(&[!lits[0]]));
+        assert!(solver.model(0) && solver.model(1));
     }
 }
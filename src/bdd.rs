@@ -0,0 +1,549 @@
+use crate::aig::{topological, And, Const, Inv, Sym, AIG};
+use crate::qmc::{Imp, Tri};
+use std::collections::HashMap;
+
+/// Edge to a BDD node, the lowest bit marks a complemented edge
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, PartialOrd, Ord)]
+pub struct Bdd(u32);
+
+impl Bdd {
+    pub const TRUE: Bdd = Bdd(0);
+    pub const FALSE: Bdd = Bdd(1);
+
+    fn node(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+    fn complemented(self) -> bool {
+        (self.0 & 1) == 1
+    }
+    fn regular(self) -> Self {
+        Self(self.0 & !1)
+    }
+    pub fn is_const(self) -> bool {
+        self.node() == 0
+    }
+}
+
+impl std::ops::Not for Bdd {
+    type Output = Self;
+    fn not(self) -> Self::Output {
+        Self(self.0 ^ 1)
+    }
+}
+
+impl std::ops::BitXor<bool> for Bdd {
+    type Output = Self;
+    fn bitxor(self, rhs: bool) -> Self::Output {
+        Self(self.0 ^ rhs as u32)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    var: usize,
+    /// then edge, never complemented
+    hi: Bdd,
+    lo: Bdd,
+}
+
+/// Reduced ordered BDD manager with complement edges
+///
+/// Node 0 is the constant one, variable `i` sits at level `level[i]` and
+/// `order[l]` is the variable at level `l`.
+pub struct Manager {
+    nodes: Vec<Node>,
+    /// unique table per variable, keyed by (then, else)
+    unique: Vec<HashMap<(Bdd, Bdd), Bdd>>,
+    /// computed table of ITE
+    cache: HashMap<(Bdd, Bdd, Bdd), Bdd>,
+    level: Vec<usize>,
+    order: Vec<usize>,
+}
+
+impl Manager {
+    pub fn new(vars: usize) -> Self {
+        Self::with_order(&(0..vars).collect::<Vec<_>>())
+    }
+
+    /// Manager with `order[l]` at level `l`
+    pub fn with_order(order: &[usize]) -> Self {
+        let mut level = vec![0; order.len()];
+        for (l, v) in order.iter().enumerate() {
+            level[*v] = l;
+        }
+        Self {
+            nodes: vec![Node {
+                var: usize::MAX,
+                hi: Bdd::TRUE,
+                lo: Bdd::TRUE,
+            }],
+            unique: vec![HashMap::new(); order.len()],
+            cache: HashMap::new(),
+            level,
+            order: order.to_vec(),
+        }
+    }
+
+    /// variables from the top level to the bottom one
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    pub fn vars(&self) -> usize {
+        self.unique.len()
+    }
+
+    fn var_of(&self, f: Bdd) -> usize {
+        self.nodes[f.node()].var
+    }
+
+    fn level_of(&self, f: Bdd) -> usize {
+        match self.var_of(f) {
+            usize::MAX => usize::MAX,
+            v => self.level[v],
+        }
+    }
+
+    fn mk(&mut self, var: usize, hi: Bdd, lo: Bdd) -> Bdd {
+        if hi == lo {
+            return hi;
+        }
+        // keep then edges regular
+        if hi.complemented() {
+            return !self.mk(var, !hi, !lo);
+        }
+        if let Some(f) = self.unique[var].get(&(hi, lo)) {
+            return *f;
+        }
+        let f = Bdd((self.nodes.len() as u32) << 1);
+        self.nodes.push(Node { var, hi, lo });
+        self.unique[var].insert((hi, lo), f);
+        f
+    }
+
+    /// (then, else) cofactors of `f` with respect to the variable at `level`
+    fn cofactors(&self, f: Bdd, level: usize) -> (Bdd, Bdd) {
+        if self.level_of(f) != level {
+            return (f, f);
+        }
+        let node = &self.nodes[f.node()];
+        (node.hi ^ f.complemented(), node.lo ^ f.complemented())
+    }
+
+    pub fn var(&mut self, var: usize) -> Bdd {
+        self.mk(var, Bdd::TRUE, Bdd::FALSE)
+    }
+
+    /// if `f` then `g` else `h`
+    pub fn ite(&mut self, f: Bdd, g: Bdd, h: Bdd) -> Bdd {
+        // terminal cases
+        if f == Bdd::TRUE {
+            return g;
+        }
+        if f == Bdd::FALSE {
+            return h;
+        }
+        // g and h in terms of f
+        let g = if g == f {
+            Bdd::TRUE
+        } else if g == !f {
+            Bdd::FALSE
+        } else {
+            g
+        };
+        let h = if h == f {
+            Bdd::FALSE
+        } else if h == !f {
+            Bdd::TRUE
+        } else {
+            h
+        };
+        if g == h {
+            return g;
+        }
+        if g == Bdd::TRUE && h == Bdd::FALSE {
+            return f;
+        }
+        if g == Bdd::FALSE && h == Bdd::TRUE {
+            return !f;
+        }
+        // standard triples, f and g regular
+        let (f, g, h) = if f.complemented() {
+            (!f, h, g)
+        } else {
+            (f, g, h)
+        };
+        let (g, h, neg) = if g.complemented() {
+            (!g, !h, true)
+        } else {
+            (g, h, false)
+        };
+
+        if let Some(r) = self.cache.get(&(f, g, h)) {
+            return *r ^ neg;
+        }
+
+        let level = [f, g, h].iter().map(|x| self.level_of(*x)).min().unwrap();
+        let var = [f, g, h]
+            .iter()
+            .find(|x| self.level_of(**x) == level)
+            .map(|x| self.var_of(*x))
+            .unwrap();
+        let (f1, f0) = self.cofactors(f, level);
+        let (g1, g0) = self.cofactors(g, level);
+        let (h1, h0) = self.cofactors(h, level);
+        let hi = self.ite(f1, g1, h1);
+        let lo = self.ite(f0, g0, h0);
+        let r = self.mk(var, hi, lo);
+        self.cache.insert((f, g, h), r);
+        r ^ neg
+    }
+
+    pub fn and(&mut self, f: Bdd, g: Bdd) -> Bdd {
+        self.ite(f, g, Bdd::FALSE)
+    }
+
+    pub fn or(&mut self, f: Bdd, g: Bdd) -> Bdd {
+        self.ite(f, Bdd::TRUE, g)
+    }
+
+    pub fn xor(&mut self, f: Bdd, g: Bdd) -> Bdd {
+        self.ite(f, !g, g)
+    }
+
+    /// BDD of an AIG, shared nodes are converted once
+    pub fn from_aig(&mut self, aig: &AIG) -> Bdd {
+        let mut memo: HashMap<*const AIG, Bdd> = HashMap::new();
+        let get = |memo: &HashMap<*const AIG, Bdd>, n: &AIG| memo[&(n as *const AIG)];
+        for node in topological([aig]) {
+            let f = match node {
+                AIG::A(And(l, r)) => {
+                    let (l, r) = (get(&memo, l), get(&memo, r));
+                    self.and(l, r)
+                }
+                AIG::I(Inv(r)) => !get(&memo, r),
+                AIG::G(Sym(i)) => self.var(*i),
+                AIG::C(Const(v)) => Bdd::FALSE ^ *v,
+            };
+            memo.insert(node as *const AIG, f);
+        }
+        get(&memo, aig)
+    }
+
+    /// (then, else) cofactors of `f` with respect to `var`, by structure only
+    fn split(&self, f: Bdd, var: usize) -> (Bdd, Bdd) {
+        if self.var_of(f) != var {
+            return (f, f);
+        }
+        let node = &self.nodes[f.node()];
+        (node.hi ^ f.complemented(), node.lo ^ f.complemented())
+    }
+
+    /// Swap the variables at `level` and `level + 1` in place
+    ///
+    /// Nodes keep their index and their function, so edges held by callers
+    /// and the computed table stay valid.
+    pub fn swap(&mut self, level: usize) {
+        let x = self.order[level];
+        let y = self.order[level + 1];
+        let moved: Vec<_> = self.unique[x]
+            .iter()
+            .filter(|((hi, lo), _)| self.var_of(*hi) == y || self.var_of(*lo) == y)
+            .map(|(k, f)| (*k, *f))
+            .collect();
+        self.order.swap(level, level + 1);
+        self.level[x] = level + 1;
+        self.level[y] = level;
+        for ((hi, lo), f) in moved {
+            self.unique[x].remove(&(hi, lo));
+            let (f11, f10) = self.split(hi, y);
+            let (f01, f00) = self.split(lo, y);
+            let hi = self.mk(x, f11, f01);
+            let lo = self.mk(x, f10, f00);
+            self.nodes[f.node()] = Node { var: y, hi, lo };
+            self.unique[y].insert((hi, lo), f);
+        }
+    }
+
+    /// Rudell's sifting: move every variable, largest first, through all
+    /// levels and leave it where `roots` are the smallest, returns that size
+    pub fn sift(&mut self, roots: &[Bdd]) -> usize {
+        let mut best = self.size(roots);
+        let mut vars: Vec<usize> = (0..self.vars()).collect();
+        let counts = self.var_counts(roots);
+        vars.sort_by_key(|v| std::cmp::Reverse(counts[*v]));
+        let bottom = self.vars().saturating_sub(1);
+        for var in vars {
+            let mut level = self.level[var];
+            let mut best_level = level;
+            // down to the bottom, then up to the top
+            while level < bottom {
+                self.swap(level);
+                level += 1;
+                let size = self.size(roots);
+                if size < best {
+                    (best, best_level) = (size, level);
+                }
+            }
+            while level > 0 {
+                self.swap(level - 1);
+                level -= 1;
+                let size = self.size(roots);
+                if size < best {
+                    (best, best_level) = (size, level);
+                }
+            }
+            while level < best_level {
+                self.swap(level);
+                level += 1;
+            }
+        }
+        best
+    }
+
+    /// number of nodes reachable from `roots` labeled by each variable
+    fn var_counts(&self, roots: &[Bdd]) -> Vec<usize> {
+        let mut counts = vec![0; self.vars()];
+        let mut seen = vec![false; self.nodes.len()];
+        let mut stack: Vec<usize> = roots.iter().map(|f| f.node()).collect();
+        while let Some(n) = stack.pop() {
+            if n == 0 || seen[n] {
+                continue;
+            }
+            seen[n] = true;
+            counts[self.nodes[n].var] += 1;
+            stack.push(self.nodes[n].hi.node());
+            stack.push(self.nodes[n].lo.node());
+        }
+        counts
+    }
+
+    pub fn eval(&self, f: Bdd, syms: &[bool]) -> bool {
+        let mut f = f;
+        while !f.is_const() {
+            let (hi, lo) = self.cofactors(f, self.level_of(f));
+            f = if syms[self.var_of(f)] { hi } else { lo };
+        }
+        f == Bdd::TRUE
+    }
+
+    /// variables `f` depends on, sorted
+    pub fn support(&self, f: Bdd) -> Vec<usize> {
+        self.var_counts(&[f])
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c > 0)
+            .map(|(v, _)| v)
+            .collect()
+    }
+
+    /// number of internal nodes reachable from `roots`
+    pub fn size(&self, roots: &[Bdd]) -> usize {
+        self.var_counts(roots).iter().sum()
+    }
+
+    /// number of minterms of `f` over all variables of the manager
+    pub fn sat_count(&self, f: Bdd) -> u128 {
+        assert!(self.vars() < 128);
+        let mut memo = HashMap::new();
+        let ones = self.sat_count_memo(f.regular(), &mut memo);
+        if f.complemented() {
+            (1 << self.vars()) - ones
+        } else {
+            ones
+        }
+    }
+
+    /// minterms of the regular edge `f`, |f| = (|f_hi| + |f_lo|) / 2
+    fn sat_count_memo(&self, f: Bdd, memo: &mut HashMap<usize, u128>) -> u128 {
+        if f.is_const() {
+            return 1 << self.vars();
+        }
+        if let Some(c) = memo.get(&f.node()) {
+            return *c;
+        }
+        let node = self.nodes[f.node()].clone();
+        let hi = self.sat_count_memo(node.hi, memo);
+        let lo = self.sat_count_memo(node.lo.regular(), memo);
+        let lo = if node.lo.complemented() {
+            (1 << self.vars()) - lo
+        } else {
+            lo
+        };
+        let c = (hi + lo) / 2;
+        memo.insert(f.node(), c);
+        c
+    }
+
+    /// disjoint cubes covering the ON-set of `f`, one per path to one
+    pub fn cubes(&self, f: Bdd) -> Vec<Imp> {
+        let mut result = vec![];
+        let mut path = vec![Tri::X; self.vars()];
+        self.cubes_rec(f, &mut path, &mut result);
+        result
+    }
+
+    fn cubes_rec(&self, f: Bdd, path: &mut Vec<Tri>, result: &mut Vec<Imp>) {
+        if f == Bdd::FALSE {
+            return;
+        }
+        if f == Bdd::TRUE {
+            result.push(Imp(path.clone()));
+            return;
+        }
+        let var = self.var_of(f);
+        let (hi, lo) = self.cofactors(f, self.level_of(f));
+        path[var] = Tri::T;
+        self.cubes_rec(hi, path, result);
+        path[var] = Tri::F;
+        self.cubes_rec(lo, path, result);
+        path[var] = Tri::X;
+    }
+
+    /// an input assignment satisfying `f`, variables off the path are false
+    pub fn sat_one(&self, f: Bdd) -> Option<Vec<bool>> {
+        if f == Bdd::FALSE {
+            return None;
+        }
+        let mut input = vec![false; self.vars()];
+        let mut f = f;
+        while !f.is_const() {
+            let var = self.var_of(f);
+            let (hi, lo) = self.cofactors(f, self.level_of(f));
+            if lo != Bdd::FALSE {
+                f = lo;
+            } else {
+                input[var] = true;
+                f = hi;
+            }
+        }
+        Some(input)
+    }
+}
+
+/// Static variable order: inputs in the order a depth first traversal of the
+/// outputs reaches them, so inputs that meet close to the leaves end up on
+/// adjacent levels; inputs outside every support go last.
+pub fn dfs_order(outputs: &[Box<AIG>], vars: usize) -> Vec<usize> {
+    let mut order = vec![];
+    let mut placed = vec![false; vars];
+    // inputs are leaves, so they come in the order the traversal reaches them
+    for aig in topological(outputs.iter().map(|o| &**o)) {
+        if let AIG::G(Sym(i)) = aig {
+            if !placed[*i] {
+                placed[*i] = true;
+                order.push(*i);
+            }
+        }
+    }
+    order.extend((0..vars).filter(|v| !placed[*v]));
+    order
+}
+
+#[cfg(test)]
+mod test {
+    use super::{dfs_order, Bdd, Manager};
+    use crate::aig::AIG;
+    use crate::qmc::Tri;
+
+    #[test]
+    fn basic() {
+        let mut m = Manager::new(3);
+        let (a, b, c) = (m.var(0), m.var(1), m.var(2));
+        let ab = m.and(a, b);
+        // De Morgan
+        let nanb = m.or(!a, !b);
+        assert_eq!(ab, !nanb);
+        assert_eq!(m.xor(a, a), Bdd::FALSE);
+        let x = m.xor(a, c);
+        let y = m.xor(c, a);
+        assert_eq!(x, y);
+        assert_eq!(m.sat_count(Bdd::TRUE), 8);
+        assert_eq!(m.sat_count(ab), 2);
+        assert_eq!(m.sat_count(!ab), 6);
+        assert_eq!(m.sat_count(x), 4);
+        let f = m.or(ab, c);
+        assert_eq!(m.sat_count(f), 5);
+        assert_eq!(m.size(&[f]), 3);
+        assert_eq!(m.sat_one(ab), Some(vec![true, true, false]));
+        assert_eq!(m.sat_one(Bdd::FALSE), None);
+    }
+
+    #[test]
+    fn aig() {
+        let a: Box<AIG> = 0.into();
+        let b: Box<AIG> = 1.into();
+        let c: Box<AIG> = 2.into();
+        let f = !(!(a.clone() & !b.clone()) & !(b & c)) & a;
+        let mut m = Manager::new(3);
+        let g = m.from_aig(&f);
+        let mut count = 0;
+        for term in 0..8 {
+            let input: Vec<_> = (0..3).map(|i| (term >> i) & 1 == 1).collect();
+            assert_eq!(m.eval(g, &input), f.eval(&input));
+            count += f.eval(&input) as u128;
+            let covered = m.cubes(g).iter().any(|cube| {
+                cube.0.iter().zip(&input).all(|(t, v)| match t {
+                    Tri::T => *v,
+                    Tri::F => !*v,
+                    Tri::X => true,
+                })
+            });
+            assert_eq!(covered, f.eval(&input));
+        }
+        assert_eq!(m.sat_count(g), count);
+    }
+
+    #[test]
+    fn support() {
+        // (a & b) | (a & !b) only depends on a
+        let a: Box<AIG> = 0.into();
+        let b: Box<AIG> = 1.into();
+        let f = (a.clone() & b.clone()) | (a & !b);
+        assert_eq!(f.support(), [0, 1]);
+        let mut m = Manager::new(3);
+        let g = m.from_aig(&f);
+        assert_eq!(m.support(g), [0]);
+        assert_eq!(m.support(Bdd::TRUE), []);
+    }
+
+    #[test]
+    fn sift() {
+        // a0 b0 + a1 b1 + a2 b2 is exponential in the order a0 a1 a2 b0 b1 b2
+        let mut m = Manager::new(6);
+        let mut f = Bdd::FALSE;
+        for i in 0..3 {
+            let (a, b) = (m.var(i), m.var(i + 3));
+            let ab = m.and(a, b);
+            f = m.or(f, ab);
+        }
+        assert_eq!(m.size(&[f]), 14);
+        let before: Vec<_> = (0..64)
+            .map(|term| m.eval(f, &(0..6).map(|i| (term >> i) & 1 == 1).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(m.sift(&[f]), 6);
+        assert_eq!(m.size(&[f]), 6);
+        let after: Vec<_> = (0..64)
+            .map(|term| m.eval(f, &(0..6).map(|i| (term >> i) & 1 == 1).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(before, after);
+        assert_eq!(m.sat_count(f), 37);
+        // same function rebuilt in the new order
+        let a0 = m.var(0);
+        let b0 = m.var(3);
+        let g = m.and(a0, b0);
+        let h = m.or(f, g);
+        assert_eq!(f, h);
+    }
+
+    #[test]
+    fn order() {
+        let a: Box<AIG> = 0.into();
+        let b: Box<AIG> = 1.into();
+        let c: Box<AIG> = 3.into();
+        let f = (a & c) & !b;
+        assert_eq!(dfs_order(&[f], 4), vec![0, 3, 1, 2]);
+        let m = Manager::with_order(&[0, 3, 1, 2]);
+        assert_eq!(m.order(), &[0, 3, 1, 2]);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::aig;
+use crate::sat::{Cnf, Lit};
+use nom::{
+    bytes::complete::tag,
+    character::complete::{i64, multispace0, multispace1, u64},
+    combinator::{all_consuming, map},
+    multi::many0,
+    sequence::{preceded, terminated, tuple},
+    Finish, IResult,
+};
+use std::fmt::{Display, Write};
+
+pub type Outputs = Vec<Box<aig::AIG>>;
+
+fn header_line(input: &[u8]) -> IResult<&[u8], (u64, u64)> {
+    map(
+        tuple((
+            tag(b"p"),
+            multispace1,
+            tag(b"cnf"),
+            multispace1,
+            u64,
+            multispace1,
+            u64,
+        )),
+        |(_, _, _, _, v, _, c)| (v, c),
+    )(input)
+}
+
+fn ints(input: &[u8]) -> IResult<&[u8], Vec<i64>> {
+    all_consuming(preceded(multispace0, many0(terminated(i64, multispace0))))(input)
+}
+
+#[derive(Debug)]
+pub enum Error<'a> {
+    /// malformed DIMACS text
+    Syntax(nom::error::Error<&'a [u8]>),
+    /// literal of a variable beyond the header
+    Range(i64),
+    /// clause not terminated by 0 at the end of the input
+    Unterminated,
+    /// number of clauses read, which differs from the header
+    Count(usize),
+}
+
+impl<'a> From<nom::error::Error<&'a [u8]>> for Error<'a> {
+    fn from(value: nom::error::Error<&'a [u8]>) -> Self {
+        Self::Syntax(value)
+    }
+}
+
+impl Display for Error<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Syntax(e) => write!(
+                f,
+                "syntax error: {:?} at {:?}",
+                e.code,
+                String::from_utf8_lossy(e.input)
+            ),
+            Error::Range(l) => write!(f, "literal {} is out of range", l),
+            Error::Unterminated => write!(f, "last clause is not terminated by 0"),
+            Error::Count(n) => write!(f, "{} clauses differ from the header", n),
+        }
+    }
+}
+
+fn int(lit: Lit) -> i64 {
+    let var = lit.var() as i64 + 1;
+    if lit.is_neg() {
+        -var
+    } else {
+        var
+    }
+}
+
+/// Write `cnf` asserting `root`, with comments that map the first `inputs`
+/// variables back to AIG inputs
+pub fn write(cnf: &Cnf, inputs: usize, root: Lit) -> String {
+    let mut out = String::new();
+    writeln!(out, "c generated by rbc").unwrap();
+    for i in 0..inputs {
+        writeln!(out, "c i{} {}", i, i + 1).unwrap();
+    }
+    writeln!(out, "c o0 {}", int(root)).unwrap();
+    writeln!(out, "p cnf {} {}", cnf.vars, cnf.clauses.len() + 1).unwrap();
+    for clause in cnf.clauses.iter().chain([&vec![root]]) {
+        for lit in clause {
+            write!(out, "{} ", int(*lit)).unwrap();
+        }
+        writeln!(out, "0").unwrap();
+    }
+    out
+}
+
+/// Combine `nodes` with `op` as a balanced tree, `empty` when there are none
+fn tree(
+    mut nodes: Outputs,
+    empty: bool,
+    op: fn(Box<aig::AIG>, Box<aig::AIG>) -> Box<aig::AIG>,
+) -> Box<aig::AIG> {
+    if nodes.is_empty() {
+        return empty.into();
+    }
+    while nodes.len() > 1 {
+        let mut next = Vec::with_capacity(nodes.len().div_ceil(2));
+        let mut iter = nodes.into_iter();
+        while let Some(a) = iter.next() {
+            next.push(match iter.next() {
+                Some(b) => op(a, b),
+                None => a,
+            });
+        }
+        nodes = next;
+    }
+    nodes.pop().unwrap()
+}
+
+/// Read a CNF as a single output, the AND of its clauses over one input per
+/// variable, both as balanced trees to keep the AIG shallow
+pub fn parse(input: &[u8]) -> Result<(usize, Outputs), Error<'_>> {
+    let mut header = None;
+    let mut body = vec![];
+    for line in input.split(|c| *c == b'\n') {
+        match line.iter().find(|c| !c.is_ascii_whitespace()) {
+            None | Some(b'c') | Some(b'%') => continue,
+            Some(b'p') => {
+                let line = line.trim_ascii();
+                header = Some(all_consuming(header_line)(line).finish()?.1);
+            }
+            _ => body.extend(ints(line).finish()?.1),
+        }
+    }
+
+    let vars = match header {
+        Some((vars, _)) => vars as usize,
+        None => body
+            .iter()
+            .map(|l| l.unsigned_abs() as usize)
+            .max()
+            .unwrap_or(0),
+    };
+
+    let mut clauses = vec![];
+    let mut clause = vec![];
+    for l in &body {
+        if *l == 0 {
+            clauses.push(tree(std::mem::take(&mut clause), false, |a, b| a | b));
+            continue;
+        }
+        let var = l.unsigned_abs() as usize;
+        if var > vars {
+            return Err(Error::Range(*l));
+        }
+        let lit: Box<aig::AIG> = (var - 1).into();
+        clause.push(if *l < 0 { !lit } else { lit });
+    }
+    if !clause.is_empty() {
+        return Err(Error::Unterminated);
+    }
+    if header.is_some_and(|(_, count)| count as usize != clauses.len()) {
+        return Err(Error::Count(clauses.len()));
+    }
+
+    Ok((vars, vec![tree(clauses, true, |a, b| a & b)]))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aig::AIG;
+    use crate::sat::{Cnf, Solver};
+
+    #[test]
+    fn roundtrip() {
+        let a: Box<AIG> = 0.into();
+        let b: Box<AIG> = 1.into();
+        let c: Box<AIG> = 2.into();
+        let f = (a.clone() ^ b) & !(a & c);
+        let mut cnf = Cnf::new(3);
+        let root = cnf.encode(std::slice::from_ref(&f))[0];
+        let text = super::write(&cnf, 3, root);
+        assert!(text.contains("c i2 3\n"));
+
+        let (vars, outputs) = super::parse(text.as_bytes()).unwrap();
+        assert_eq!(vars, cnf.vars);
+        // the CNF is satisfiable exactly under the inputs where f is true
+        for term in 0..8 {
+            let input: Vec<_> = (0..3).map(|i| (term >> i) & 1 == 1).collect();
+            let mut cnf = Cnf::new(vars);
+            let g = cnf.encode(&outputs)[0];
+            let mut s = Solver::from(&cnf);
+            let assumptions: Vec<_> = input
+                .iter()
+                .enumerate()
+                .map(|(i, v)| crate::sat::Lit::new(i, !v))
+                .chain([g])
+                .collect();
+            assert_eq!(s.solve(&assumptions), f.eval(&input));
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let (vars, outputs) = super::parse(b"c x\np cnf 2 2\n1 -2 0\n2\n0\n").unwrap();
+        assert_eq!(vars, 2);
+        let f = &outputs[0];
+        assert!(f.eval(&[true, true]));
+        assert!(!f.eval(&[true, false]));
+        assert!(!f.eval(&[false, true]));
+        assert!(matches!(
+            super::parse(b"p cnf 1 1\n2 0\n"),
+            Err(super::Error::Range(2))
+        ));
+        assert!(matches!(
+            super::parse(b"p cnf 1 1\n1\n"),
+            Err(super::Error::Unterminated)
+        ));
+        assert!(matches!(
+            super::parse(b"p cnf 1 1\n1 x 0\n"),
+            Err(super::Error::Syntax(_))
+        ));
+        assert!(matches!(
+            super::parse(b"p cnf 2 3\n1 0\n2 0\n"),
+            Err(super::Error::Count(2))
+        ));
+        let (_, outputs) = super::parse(b"p cnf 1 0\n").unwrap();
+        assert!(outputs[0].eval(&[false]));
+    }
+
+    #[test]
+    fn large() {
+        // 10^5 random 3-clauses build a shallow AIG
+        let (vars, clauses) = (300, 100_000);
+        let mut rng = crate::testing::Rng::new(0x9e3779b97f4a7c15);
+        let body: Vec<[i64; 3]> = (0..clauses)
+            .map(|_| {
+                [0; 3].map(|_| {
+                    let var = rng.below(vars) as i64 + 1;
+                    if rng.below(2) == 1 {
+                        -var
+                    } else {
+                        var
+                    }
+                })
+            })
+            .collect();
+        let mut text = format!("p cnf {} {}\n", vars, clauses);
+        for c in &body {
+            text += &format!("{} {} {} 0\n", c[0], c[1], c[2]);
+        }
+        let (_, outputs) = super::parse(text.as_bytes()).unwrap();
+        // 17 levels of ANDs over 2 levels of ORs per clause
+        assert!(crate::aig::depth(&outputs) <= 19);
+        let input: Vec<bool> = (0..vars).map(|_| rng.below(2) == 1).collect();
+        let expected = body.iter().all(|c| {
+            c.iter()
+                .any(|l| input[l.unsigned_abs() as usize - 1] == (*l > 0))
+        });
+        assert_eq!(outputs[0].eval(&input), expected);
+    }
+}
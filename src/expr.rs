@@ -1,19 +1,85 @@
 use crate::aig;
 use lalrpop_util::lalrpop_mod;
+use std::fmt::Display;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Expr {
+    Const(bool),
     Term(char),
+    /// input by index, as built by factoring
+    Var(usize),
     Not(Box<Expr>),
     Or(Box<Expr>, Box<Expr>),
     And(Box<Expr>, Box<Expr>),
     Xor(Box<Expr>, Box<Expr>),
 }
 
+impl Expr {
+    /// number of variable occurrences
+    pub fn literals(&self) -> usize {
+        match self {
+            Expr::Const(_) => 0,
+            Expr::Term(_) | Expr::Var(_) => 1,
+            Expr::Not(e) => e.literals(),
+            Expr::Or(l, r) | Expr::And(l, r) | Expr::Xor(l, r) => l.literals() + r.literals(),
+        }
+    }
+
+    /// algebraic notation like `a' (b + c)`, input `i` being named `names[i]`
+    pub fn named(&self, names: &[String]) -> String {
+        self.named_in(names, false)
+    }
+
+    /// `product` is set for an operand of a product, where sums need parentheses
+    fn named_in(&self, names: &[String], product: bool) -> String {
+        match self {
+            Expr::Const(v) => (*v as u8).to_string(),
+            Expr::Term(t) => t.to_string(),
+            Expr::Var(i) => names[*i].clone(),
+            Expr::Not(e) => match **e {
+                Expr::Term(_) | Expr::Var(_) | Expr::Const(_) => format!("{}'", e.named(names)),
+                _ => format!("({})'", e.named(names)),
+            },
+            Expr::And(l, r) => format!("{} {}", l.named_in(names, true), r.named_in(names, true)),
+            Expr::Or(l, r) | Expr::Xor(l, r) => {
+                let op = if matches!(self, Expr::Or(..)) {
+                    "+"
+                } else {
+                    "^"
+                };
+                let sum = format!("{} {} {}", l.named(names), op, r.named(names));
+                if product {
+                    format!("({})", sum)
+                } else {
+                    sum
+                }
+            }
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Const(v) => write!(f, "{}", *v as u8),
+            Expr::Term(t) => write!(f, "{}", t),
+            // inputs by index print like AIG inputs, which the grammar does
+            // not read back, factored forms are printed with `named`
+            Expr::Var(i) => write!(f, "i{}", i),
+            Expr::Not(e) => write!(f, "(!{})", e),
+            Expr::Or(l, r) => write!(f, "({}|{})", l, r),
+            Expr::And(l, r) => write!(f, "({}&{})", l, r),
+            Expr::Xor(l, r) => write!(f, "({}^{})", l, r),
+        }
+    }
+}
+
 impl From<Box<Expr>> for Box<aig::AIG> {
     fn from(val: Box<Expr>) -> Self {
         match *val {
+            Expr::Const(v) => v.into(),
             Expr::Term(v) => (v as usize - 'A' as usize).into(),
+            Expr::Var(i) => i.into(),
             Expr::Not(v) => {
                 let v: Box<aig::AIG> = v.into();
                 !v
@@ -37,7 +103,7 @@ impl From<Box<Expr>> for Box<aig::AIG> {
     }
 }
 
-lalrpop_mod!(pub calculator1); // synthesized by LALRPOP
+lalrpop_mod!(#[allow(clippy::all)] pub calculator1); // synthesized by LALRPOP
 
 #[test]
 fn calculator1() {
@@ -75,4 +141,16 @@ fn calculator1() {
             ))))
         ))
     );
+
+    for text in ["((A&(!B))|(!(A&B)))", "(1&(A^0))"] {
+        let e = calculator1::ExprParser::new().parse(text).unwrap();
+        assert_eq!(e.to_string(), text);
+    }
+    assert_eq!(
+        calculator1::ExprParser::new()
+            .parse("((A&B)|(A&C))")
+            .unwrap()
+            .literals(),
+        4
+    );
 }
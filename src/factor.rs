@@ -0,0 +1,288 @@
+use crate::expr::Expr;
+use crate::qmc::{Imp, Tri};
+
+/// Literal `2 * var + negated`
+pub type Lit = usize;
+/// Product of literals, sorted without duplicates
+pub type Cube = Vec<Lit>;
+/// Algebraic sum of products
+pub type Sop = Vec<Cube>;
+
+pub fn from_imps<'a>(cover: impl IntoIterator<Item = &'a Imp>) -> Sop {
+    let mut sop: Sop = cover
+        .into_iter()
+        .map(|imp| {
+            imp.0
+                .iter()
+                .enumerate()
+                .filter_map(|(v, t)| match t {
+                    Tri::T => Some(2 * v),
+                    Tri::F => Some(2 * v + 1),
+                    Tri::X => None,
+                })
+                .collect()
+        })
+        .collect();
+    sop.sort();
+    sop
+}
+
+pub fn literals(sop: &Sop) -> usize {
+    sop.iter().map(Vec::len).sum()
+}
+
+/// `a / b` if `b` is contained in `a`
+fn cube_div(a: &Cube, b: &Cube) -> Option<Cube> {
+    if b.iter().all(|l| a.contains(l)) {
+        Some(a.iter().filter(|l| !b.contains(l)).copied().collect())
+    } else {
+        None
+    }
+}
+
+fn cube_mul(a: &Cube, b: &Cube) -> Option<Cube> {
+    let mut c: Cube = a.iter().chain(b).copied().collect();
+    c.sort();
+    c.dedup();
+    // x & !x vanishes algebraically
+    if c.windows(2).any(|w| w[0] ^ 1 == w[1]) {
+        None
+    } else {
+        Some(c)
+    }
+}
+
+/// Largest cube dividing every cube of `sop`
+fn common(sop: &Sop) -> Cube {
+    match sop.split_first() {
+        Some((first, rest)) => first
+            .iter()
+            .filter(|l| rest.iter().all(|c| c.contains(l)))
+            .copied()
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// Weak division, `f = q * d + r` with `q` as large as possible
+pub fn divide(f: &Sop, d: &Sop) -> (Sop, Sop) {
+    let mut quotient: Option<Sop> = None;
+    for dc in d {
+        let q: Sop = f.iter().filter_map(|c| cube_div(c, dc)).collect();
+        quotient = Some(match quotient {
+            None => q,
+            Some(prev) => prev.into_iter().filter(|c| q.contains(c)).collect(),
+        });
+    }
+    let mut quotient = quotient.unwrap_or_default();
+    quotient.sort();
+    let product: Sop = quotient
+        .iter()
+        .flat_map(|q| d.iter().filter_map(|dc| cube_mul(q, dc)))
+        .collect();
+    let remainder = f.iter().filter(|c| !product.contains(c)).cloned().collect();
+    (quotient, remainder)
+}
+
+/// All kernels of `f` with their co-kernels, as `(co-kernel, kernel)`
+pub fn kernels(f: &Sop) -> Vec<(Cube, Sop)> {
+    fn recurse(f: &Sop, co: &Cube, start: Lit, result: &mut Vec<(Cube, Sop)>) {
+        let max = f.iter().flatten().copied().max().map_or(0, |l| l + 1);
+        for l in start..max {
+            let with: Sop = f.iter().filter(|c| c.contains(&l)).cloned().collect();
+            if with.len() < 2 {
+                continue;
+            }
+            let c = common(&with);
+            // already found from a smaller literal
+            if c.iter().any(|x| *x < l) {
+                continue;
+            }
+            let kernel: Sop = with.iter().map(|x| cube_div(x, &c).unwrap()).collect();
+            let co = cube_mul(co, &c).unwrap();
+            recurse(&kernel, &co, l + 1, result);
+        }
+        if f.len() > 1 && common(f).is_empty() {
+            let mut k = f.clone();
+            k.sort();
+            let pair = (co.clone(), k);
+            if !result.contains(&pair) {
+                result.push(pair);
+            }
+        }
+    }
+    let mut result = vec![];
+    let c = common(f);
+    let f: Sop = f.iter().map(|x| cube_div(x, &c).unwrap()).collect();
+    recurse(&f, &c, 0, &mut result);
+    result
+}
+
+fn literal(l: Lit) -> Box<Expr> {
+    let term = Box::new(Expr::Var(l / 2));
+    if l % 2 == 1 {
+        Box::new(Expr::Not(term))
+    } else {
+        term
+    }
+}
+
+fn product(cube: &Cube) -> Box<Expr> {
+    cube.iter()
+        .map(|l| literal(*l))
+        .reduce(|a, b| Box::new(Expr::And(a, b)))
+        .unwrap_or(Box::new(Expr::Const(true)))
+}
+
+fn sum(sop: &Sop) -> Box<Expr> {
+    sop.iter()
+        .map(product)
+        .reduce(|a, b| Box::new(Expr::Or(a, b)))
+        .unwrap_or(Box::new(Expr::Const(false)))
+}
+
+/// Factored form of `f`, recursively dividing by the kernel or literal that
+/// saves the most literals
+pub fn factor_sop(f: &Sop) -> Box<Expr> {
+    if f.len() < 2 {
+        return sum(f);
+    }
+    let c = common(f);
+    if !c.is_empty() {
+        let rest: Sop = f.iter().map(|x| cube_div(x, &c).unwrap()).collect();
+        return Box::new(Expr::And(product(&c), factor_sop(&rest)));
+    }
+
+    let mut divisors: Vec<Sop> = kernels(f)
+        .into_iter()
+        .map(|(_, k)| k)
+        .filter(|k| k != f)
+        .collect();
+    let mut lits: Vec<Lit> = f.iter().flatten().copied().collect();
+    lits.sort();
+    lits.dedup();
+    divisors.extend(lits.into_iter().map(|l| vec![vec![l]]));
+
+    let mut best: Option<(usize, Sop, Sop, Sop)> = None;
+    for d in divisors {
+        let (q, r) = divide(f, &d);
+        if q.is_empty() || q == [vec![]] {
+            continue;
+        }
+        let cost = literals(&q) + literals(&d) + literals(&r);
+        if cost < literals(f) && best.as_ref().is_none_or(|(c, ..)| cost < *c) {
+            best = Some((cost, q, d, r));
+        }
+    }
+    match best {
+        Some((_, q, d, r)) => {
+            let product = Box::new(Expr::And(factor_sop(&q), factor_sop(&d)));
+            if r.is_empty() {
+                product
+            } else {
+                Box::new(Expr::Or(product, factor_sop(&r)))
+            }
+        }
+        None => sum(f),
+    }
+}
+
+/// Factored form of a cover, variable `i` being `Var(i)`
+pub fn factor<'a>(cover: impl IntoIterator<Item = &'a Imp>) -> Box<Expr> {
+    factor_sop(&from_imps(cover))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{divide, factor, factor_sop, kernels, literals, Sop};
+    use crate::aig::AIG;
+    use crate::qmc::{Imp, Tri};
+
+    // a = 0, b = 2, c = 4, d = 6, e = 8, !a = 1
+    fn sop(text: &str) -> Sop {
+        text.split('+')
+            .map(|c| {
+                let mut cube: Vec<usize> = vec![];
+                for ch in c.trim().chars() {
+                    match ch {
+                        '\'' => *cube.last_mut().unwrap() += 1,
+                        _ => cube.push(2 * (ch as usize - 'a' as usize)),
+                    }
+                }
+                cube.sort();
+                cube
+            })
+            .collect()
+    }
+
+    #[test]
+    fn division() {
+        let f = sop("ac + ad + bc + bd + e");
+        let (q, r) = divide(&f, &sop("a + b"));
+        assert_eq!(q, sop("c + d"));
+        assert_eq!(r, sop("e"));
+        let (q, r) = divide(&f, &sop("c"));
+        assert_eq!(q, sop("a + b"));
+        assert_eq!(r, sop("ad + bd + e"));
+    }
+
+    #[test]
+    fn kernel() {
+        let f = sop("ace + bce + de + g");
+        let ks = kernels(&f);
+        assert!(ks.contains(&(sop("ce")[0].clone(), sop("a + b"))));
+        assert!(ks.contains(&(sop("e")[0].clone(), sop("ac + bc + d"))));
+        assert!(ks.contains(&(vec![], f.clone())));
+        assert_eq!(ks.len(), 3);
+    }
+
+    #[test]
+    fn factored() {
+        let f = sop("ac + ad + bc + bd + e");
+        let e = factor_sop(&f);
+        assert_eq!(e.literals(), 5);
+        assert!(literals(&f) > e.literals());
+
+        // a'b + a'c from Imps, factored into a'(b + c)
+        let cover = [
+            Imp(vec![Tri::F, Tri::T, Tri::X]),
+            Imp(vec![Tri::F, Tri::X, Tri::T]),
+        ];
+        let e = factor(&cover);
+        assert_eq!(e.literals(), 3);
+        let aig: Box<AIG> = e.into();
+        for term in 0..8 {
+            let input: Vec<_> = (0..3).map(|i| (term >> i) & 1 == 1).collect();
+            assert_eq!(aig.eval(&input), !input[0] && (input[1] || input[2]));
+        }
+        assert_eq!(factor(&[]).literals(), 0);
+    }
+
+    #[test]
+    fn wide() {
+        // x0 x27 + x0 x28' over 30 inputs, factored into x0 (x27 + x28')
+        let cube = |lits: &[(usize, Tri)]| {
+            let mut imp = vec![Tri::X; 30];
+            for (v, t) in lits {
+                imp[*v] = *t;
+            }
+            Imp(imp)
+        };
+        let cover = [
+            cube(&[(0, Tri::T), (27, Tri::T)]),
+            cube(&[(0, Tri::T), (28, Tri::F)]),
+        ];
+        let e = factor(&cover);
+        let names: Vec<String> = (0..30).map(|i| format!("x{}", i)).collect();
+        assert_eq!(e.named(&names), "x0 (x27 + x28')");
+        assert_eq!(e.to_string(), "(i0&(i27|(!i28)))");
+        let aig: Box<AIG> = e.into();
+        let mut input = vec![false; 30];
+        input[0] = true;
+        assert!(aig.eval(&input));
+        input[28] = true;
+        assert!(!aig.eval(&input));
+        input[27] = true;
+        assert!(aig.eval(&input));
+    }
+}
@@ -0,0 +1,322 @@
+use crate::qmc::{Imp, Tri};
+use crate::truth::Truth;
+use std::fmt::Write;
+
+/// Karnaugh map of a function of 2 to 6 inputs, the first half of the
+/// inputs along the rows and the rest along the columns, both Gray coded
+pub struct Kmap<'a> {
+    truth: &'a Truth,
+    groups: &'a [Imp],
+    /// name of every input
+    names: &'a [String],
+    rows: usize,
+}
+
+/// label of group `g`
+fn label(g: usize) -> char {
+    char::from_u32('a' as u32 + (g % 26) as u32).unwrap()
+}
+
+/// text escaped for XML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn gray(k: usize) -> usize {
+    k ^ (k >> 1)
+}
+
+impl<'a> Kmap<'a> {
+    pub fn new(truth: &'a Truth, groups: &'a [Imp], names: &'a [String]) -> Self {
+        assert!((2..=6).contains(&truth.vars()));
+        assert_eq!(names.len(), truth.vars());
+        Self {
+            truth,
+            groups,
+            names,
+            rows: truth.vars() / 2,
+        }
+    }
+
+    /// label of the inputs along an axis, concatenated when they are single
+    /// characters
+    fn label(&self, inputs: std::ops::Range<usize>) -> String {
+        let names = &self.names[inputs];
+        if names.iter().all(|n| n.chars().count() == 1) {
+            names.concat()
+        } else {
+            names.join(",")
+        }
+    }
+
+    fn cols(&self) -> usize {
+        self.truth.vars() - self.rows
+    }
+
+    /// values of `vars` inputs from `first` at position `k` of an axis, the
+    /// first input being the most significant bit
+    fn axis(first: usize, vars: usize, k: usize) -> Vec<(usize, bool)> {
+        let g = gray(k);
+        (0..vars)
+            .map(|j| (first + j, (g >> (vars - 1 - j)) & 1 == 1))
+            .collect()
+    }
+
+    fn minterm(&self, row: usize, col: usize) -> usize {
+        Self::axis(0, self.rows, row)
+            .into_iter()
+            .chain(Self::axis(self.rows, self.cols(), col))
+            .fold(0, |acc, (i, v)| acc | ((v as usize) << i))
+    }
+
+    fn covers(imp: &Imp, m: usize) -> bool {
+        imp.0.iter().enumerate().all(|(i, t)| match t {
+            Tri::T => (m >> i) & 1 == 1,
+            Tri::F => (m >> i) & 1 == 0,
+            Tri::X => true,
+        })
+    }
+
+    fn cell(&self, row: usize, col: usize) -> String {
+        let m = self.minterm(row, col);
+        let mut cell = (self.truth.get(m) as u8).to_string();
+        for (g, imp) in self.groups.iter().enumerate() {
+            if Self::covers(imp, m) {
+                cell.push(label(g));
+            }
+        }
+        cell
+    }
+
+    fn code(k: usize, vars: usize) -> String {
+        format!("{:0width$b}", gray(k), width = vars)
+    }
+
+    /// the map as text, with the groups listed below it
+    pub fn ascii(&self) -> String {
+        let (rows, cols) = (1 << self.rows, 1 << self.cols());
+        let row_names = self.label(0..self.rows);
+        let col_names = self.label(self.rows..self.truth.vars());
+        let width = (0..rows)
+            .flat_map(|r| (0..cols).map(move |c| (r, c)))
+            .map(|(r, c)| self.cell(r, c).len())
+            .chain([self.cols()])
+            .max()
+            .unwrap();
+        let margin = row_names.chars().count().max(self.rows) + 1;
+
+        let mut out = String::new();
+        writeln!(out, "{:margin$} {}", "", col_names).unwrap();
+        write!(out, "{:margin$}", row_names).unwrap();
+        for c in 0..cols {
+            write!(out, " {:>width$}", Self::code(c, self.cols())).unwrap();
+        }
+        writeln!(out).unwrap();
+        for r in 0..rows {
+            write!(out, "{:margin$}", Self::code(r, self.rows)).unwrap();
+            for c in 0..cols {
+                write!(out, " {:>width$}", self.cell(r, c)).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+        for (g, imp) in self.groups.iter().enumerate() {
+            writeln!(out, "{}: {} ({})", label(g), imp.product(self.names), imp).unwrap();
+        }
+        out
+    }
+
+    /// positions along an axis of `vars` inputs from `first` covered by
+    /// `imp`, as runs of consecutive positions
+    fn runs(imp: &Imp, first: usize, vars: usize) -> Vec<(usize, usize)> {
+        let covered: Vec<bool> = (0..1 << vars)
+            .map(|k| {
+                Self::axis(first, vars, k)
+                    .iter()
+                    .all(|(i, v)| match imp.0[*i] {
+                        Tri::T => *v,
+                        Tri::F => !*v,
+                        Tri::X => true,
+                    })
+            })
+            .collect();
+        let mut runs = vec![];
+        let mut k = 0;
+        while k < covered.len() {
+            if covered[k] {
+                let start = k;
+                while k < covered.len() && covered[k] {
+                    k += 1;
+                }
+                runs.push((start, k - start));
+            } else {
+                k += 1;
+            }
+        }
+        runs
+    }
+
+    /// the map as an SVG image, every group drawn as labeled rectangles
+    /// that are split where it wraps around an edge
+    pub fn svg(&self) -> String {
+        const CELL: usize = 48;
+        const COLORS: [&str; 6] = [
+            "#d62728", "#1f77b4", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b",
+        ];
+        let (rows, cols) = (1 << self.rows, 1 << self.cols());
+        let (x0, y0) = (CELL, CELL);
+        let (w, h) = (
+            x0 + cols * CELL + 8,
+            y0 + rows * CELL + 8 + 16 * self.groups.len(),
+        );
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"14\">",
+            w, h
+        )
+        .unwrap();
+        let row_names = self.label(0..self.rows);
+        let col_names = self.label(self.rows..self.truth.vars());
+        writeln!(
+            out,
+            "<text x=\"4\" y=\"16\">{}\\{}</text>",
+            escape(&row_names),
+            escape(&col_names)
+        )
+        .unwrap();
+        for c in 0..cols {
+            writeln!(
+                out,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                x0 + c * CELL + CELL / 2,
+                y0 - 8,
+                Self::code(c, self.cols())
+            )
+            .unwrap();
+        }
+        for r in 0..rows {
+            writeln!(
+                out,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+                x0 - 6,
+                y0 + r * CELL + CELL / 2 + 5,
+                Self::code(r, self.rows)
+            )
+            .unwrap();
+            for c in 0..cols {
+                let (x, y) = (x0 + c * CELL, y0 + r * CELL);
+                writeln!(
+                    out,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>",
+                    x, y, CELL, CELL
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                    x + CELL / 2,
+                    y + CELL / 2 + 5,
+                    self.truth.get(self.minterm(r, c)) as u8
+                )
+                .unwrap();
+            }
+        }
+        for (g, imp) in self.groups.iter().enumerate() {
+            let color = COLORS[g % COLORS.len()];
+            let inset = 3 + 3 * (g % 4);
+            for (r, rn) in Self::runs(imp, 0, self.rows) {
+                for (c, cn) in Self::runs(imp, self.rows, self.cols()) {
+                    let (x, y) = (x0 + c * CELL + inset, y0 + r * CELL + inset);
+                    writeln!(
+                        out,
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"10\" fill=\"{}\" fill-opacity=\"0.15\" stroke=\"{}\" stroke-width=\"2\"/>",
+                        x,
+                        y,
+                        cn * CELL - 2 * inset,
+                        rn * CELL - 2 * inset,
+                        color,
+                        color
+                    )
+                    .unwrap();
+                    writeln!(
+                        out,
+                        "<text x=\"{}\" y=\"{}\" fill=\"{}\" font-size=\"11\">{}</text>",
+                        x + 3,
+                        y + 11,
+                        color,
+                        label(g)
+                    )
+                    .unwrap();
+                }
+            }
+            writeln!(
+                out,
+                "<text x=\"4\" y=\"{}\" fill=\"{}\">{}: {} ({})</text>",
+                y0 + rows * CELL + 20 + 16 * g,
+                color,
+                label(g),
+                escape(&imp.product(self.names)),
+                imp
+            )
+            .unwrap();
+        }
+        writeln!(out, "</svg>").unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Kmap;
+    use crate::qmc::{Imp, Tri};
+    use crate::truth::Truth;
+
+    #[test]
+    fn render() {
+        // A'B + C'D over 4 inputs, A and B on the rows
+        let t = Truth::new(4, |x| (!x[0] && x[1]) || (!x[2] && x[3]));
+        let groups = [
+            Imp(vec![Tri::F, Tri::T, Tri::X, Tri::X]),
+            Imp(vec![Tri::X, Tri::X, Tri::F, Tri::T]),
+        ];
+        let names = ["A", "B", "C", "D"].map(String::from);
+        let map = Kmap::new(&t, &groups, &names);
+        let text = map.ascii();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0].trim(), "CD");
+        assert_eq!(
+            lines[1].split_whitespace().collect::<Vec<_>>(),
+            ["AB", "00", "01", "11", "10"]
+        );
+        // row 01 is A'B, column 01 is C'D
+        assert_eq!(
+            lines[3].split_whitespace().collect::<Vec<_>>(),
+            ["01", "1a", "1ab", "1a", "1a"]
+        );
+        assert_eq!(
+            lines[4].split_whitespace().collect::<Vec<_>>(),
+            ["11", "0", "1b", "0", "0"]
+        );
+        assert_eq!(lines[6], "a: A' B (01--)");
+
+        let svg = map.svg();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("rx=\"10\"").count(), 2);
+
+        // a group wrapping around the columns is drawn in two pieces
+        let names = ["in[0]", "in[1]", "in[2]"].map(String::from);
+        let t = Truth::new(3, |x| !x[2]);
+        let groups = [Imp(vec![Tri::X, Tri::X, Tri::F])];
+        let map = Kmap::new(&t, &groups, &names);
+        assert!(map.ascii().starts_with("       in[1],in[2]\nin[0]"));
+        assert!(map.ascii().ends_with("a: in[2]' (--0)\n"));
+        assert_eq!(map.svg().matches("rx=\"10\"").count(), 2);
+        let t = Truth::new(3, |x| !x[1]);
+        let groups = [Imp(vec![Tri::X, Tri::F, Tri::X])];
+        let map = Kmap::new(&t, &groups, &names);
+        assert_eq!(map.svg().matches("rx=\"10\"").count(), 1);
+    }
+}
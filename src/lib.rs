@@ -1,12 +1,29 @@
 pub mod aag;
 pub mod aig;
+pub mod bdd;
+pub mod dimacs;
 pub mod expr;
+pub mod factor;
+pub mod kmap;
+pub mod lut;
+pub mod map;
+pub mod opt;
+pub mod pla;
 pub mod qmc;
+pub mod sat;
+pub mod stats;
+#[cfg(test)]
+mod testing;
+pub mod trace;
+pub mod truth;
 
 use pyo3::prelude::*;
-use qmc::{reduce, Imp, Tri};
+#[cfg(feature = "parallel")]
+use qmc::par_reduce as reduce;
+#[cfg(not(feature = "parallel"))]
+use qmc::reduce;
+use qmc::{cover, sorted, Imp, Tri};
 use std::collections::{HashMap, HashSet};
-use std::ops::Sub;
 
 #[pyfunction]
 fn minimize(minterms: Vec<usize>, variables: HashMap<String, usize>) -> PyResult<String> {
@@ -23,41 +40,13 @@ fn minimize(minterms: Vec<usize>, variables: HashMap<String, usize>) -> PyResult
         }
         m.insert(Imp(imp));
     }
-    let mut columns = m.clone();
-    let rows = reduce(&m);
-    let mut chosen: HashSet<Imp> = HashSet::new();
-
-    let mut fallback = false;
-    loop {
-        let mut covered: HashSet<Imp> = HashSet::new();
-
-        for col in &columns {
-            let cover: Vec<_> = rows.iter().filter(|p| p.containes(col)).collect();
-            if cover.len() == 1 || fallback {
-                fallback = false;
-                chosen.insert(cover[0].clone());
-                for col in &columns {
-                    if cover[0].containes(col) {
-                        covered.insert(col.clone());
-                    }
-                }
-            }
-        }
-
-        columns = columns.sub(&covered);
-
-        if columns.is_empty() {
-            break;
-        }
-
-        fallback = covered.is_empty();
-    }
+    let chosen = cover(&m, &reduce(&m));
 
     let mut expr = vec![];
-    for x in &chosen {
+    for x in &sorted(&chosen) {
         let mut comp = vec![];
         for (i, y) in x.0.iter().enumerate() {
-            let v = variables.iter().find(|(k, v)| **v == i).unwrap().0;
+            let v = variables.iter().find(|(_, v)| **v == i).unwrap().0;
             match y {
                 Tri::T => comp.push(v.to_string()),
                 Tri::F => comp.push("~ ".to_string() + v),
@@ -0,0 +1,368 @@
+use crate::opt::{self, inverted, node, Cut, Graph, Lit};
+use std::fmt::Write;
+
+/// Number of priority cuts kept per node
+const CUTS: usize = 8;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// minimum depth
+    Depth,
+    /// minimum depth, then as few LUTs as possible without increasing it
+    Area,
+}
+
+/// LUT computing node `output` from the nodes `inputs`, input `i` being
+/// variable `i` of `truth`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lut {
+    pub output: usize,
+    pub inputs: Vec<usize>,
+    pub truth: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mapping {
+    pub inputs: usize,
+    pub luts: Vec<Lut>,
+    pub outputs: Vec<Lit>,
+    pub depth: usize,
+}
+
+struct Mapper<'a> {
+    g: &'a Graph,
+    k: usize,
+    refs: Vec<usize>,
+    /// priority cuts of every node, the chosen one first and the trivial one last
+    cuts: Vec<Vec<Cut>>,
+    arrival: Vec<usize>,
+    flow: Vec<f64>,
+}
+
+impl Mapper<'_> {
+    fn arrival(&self, cut: &Cut) -> usize {
+        cut.leaves
+            .iter()
+            .map(|l| self.arrival[*l])
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    fn flow(&self, cut: &Cut) -> f64 {
+        1.0 + cut
+            .leaves
+            .iter()
+            .map(|l| self.flow[*l] / self.refs[*l].max(1) as f64)
+            .sum::<f64>()
+    }
+
+    /// Recompute the priority cuts of every AND node from those of its
+    /// fanins, ordered by depth first or, under `required` times, by area flow
+    fn pass(&mut self, required: Option<&[usize]>) {
+        let g = self.g;
+        for n in g.inputs + 1..g.len() {
+            let (a, b) = g.fanins(n);
+            let mut candidates: Vec<Cut> = vec![];
+            if required.is_some() {
+                // the previous choice keeps the required time met
+                candidates.push(self.cuts[n][0].clone());
+            }
+            for ca in &self.cuts[node(a)] {
+                for cb in &self.cuts[node(b)] {
+                    if let Some(cut) = Cut::merge(ca, a, cb, b, self.k) {
+                        candidates.push(cut);
+                    }
+                }
+            }
+            let mut scored: Vec<(usize, f64, Cut)> = candidates
+                .into_iter()
+                .map(|c| (self.arrival(&c), self.flow(&c), c))
+                .collect();
+            match required {
+                None => scored.sort_by(|x, y| {
+                    (x.0, x.2.leaves.len())
+                        .cmp(&(y.0, y.2.leaves.len()))
+                        .then(x.1.total_cmp(&y.1))
+                }),
+                Some(required) => scored.sort_by(|x, y| {
+                    (x.0 > required[n])
+                        .cmp(&(y.0 > required[n]))
+                        .then(x.1.total_cmp(&y.1))
+                        .then(x.0.cmp(&y.0))
+                }),
+            }
+            let mut result: Vec<Cut> = vec![];
+            for (_, _, cut) in scored {
+                if result.len() == CUTS {
+                    break;
+                }
+                if !result.iter().any(|c| c.dominates(&cut)) {
+                    result.push(cut);
+                }
+            }
+            self.arrival[n] = self.arrival(&result[0]);
+            self.flow[n] = self.flow(&result[0]);
+            result.push(Cut::trivial(n));
+            self.cuts[n] = result;
+        }
+    }
+
+    /// roots of the LUTs covering the outputs, in topological order
+    fn cover(&self) -> Vec<usize> {
+        let g = self.g;
+        let mut used = vec![false; g.len()];
+        for o in &g.outputs {
+            used[node(*o)] = true;
+        }
+        for n in (g.inputs + 1..g.len()).rev() {
+            if used[n] {
+                for l in &self.cuts[n][0].leaves {
+                    used[*l] = true;
+                }
+            }
+        }
+        (g.inputs + 1..g.len()).filter(|n| used[*n]).collect()
+    }
+
+    fn required(&self, depth: usize) -> Vec<usize> {
+        let mut required = vec![usize::MAX; self.g.len()];
+        for o in &self.g.outputs {
+            required[node(*o)] = depth;
+        }
+        for n in self.cover().into_iter().rev() {
+            for l in &self.cuts[n][0].leaves {
+                required[*l] = required[*l].min(required[n] - 1);
+            }
+        }
+        required
+    }
+
+    /// Reference the leaves of the chosen cut of `n`, or dereference them,
+    /// returning the number of LUTs that become used or unused
+    fn reference(&self, n: usize, refs: &mut [usize], add: bool) -> usize {
+        let mut count = 0;
+        let mut stack = vec![n];
+        while let Some(n) = stack.pop() {
+            for &l in &self.cuts[n][0].leaves {
+                if !self.g.is_and(l) {
+                    continue;
+                }
+                if add {
+                    refs[l] += 1;
+                } else {
+                    refs[l] -= 1;
+                }
+                // the LUT of `l` just became used, or unused
+                if refs[l] == add as usize {
+                    count += 1;
+                    stack.push(l);
+                }
+            }
+        }
+        count
+    }
+
+    /// Choose for every LUT the cut adding the fewest LUTs to the mapping
+    /// within its required time
+    fn exact(&mut self, required: &[usize]) {
+        let g = self.g;
+        let mut refs = vec![0; g.len()];
+        for o in &g.outputs {
+            refs[node(*o)] += 1;
+        }
+        for n in self.cover() {
+            for l in &self.cuts[n][0].leaves {
+                refs[*l] += 1;
+            }
+        }
+        for n in g.inputs + 1..g.len() {
+            if refs[n] > 0 {
+                self.reference(n, &mut refs, false);
+                let mut best = (usize::MAX, usize::MAX, 0);
+                for i in 0..self.cuts[n].len() - 1 {
+                    let arrival = self.arrival(&self.cuts[n][i]);
+                    if arrival > required[n] {
+                        continue;
+                    }
+                    self.cuts[n].swap(0, i);
+                    let area = self.reference(n, &mut refs, true);
+                    self.reference(n, &mut refs, false);
+                    self.cuts[n].swap(0, i);
+                    if (area, arrival) < (best.0, best.1) {
+                        best = (area, arrival, i);
+                    }
+                }
+                self.cuts[n].swap(0, best.2);
+                self.reference(n, &mut refs, true);
+            }
+            self.arrival[n] = self.arrival(&self.cuts[n][0]);
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.g
+            .outputs
+            .iter()
+            .map(|o| self.arrival[node(*o)])
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Map the graph onto `k`-input LUTs with priority cuts
+pub fn map(g: &Graph, k: usize, mode: Mode) -> Mapping {
+    assert!((2..=6).contains(&k));
+    let mut cuts: Vec<Vec<Cut>> = vec![vec![Cut {
+        leaves: vec![],
+        truth: 0,
+    }]];
+    cuts.extend((1..g.len()).map(|n| vec![Cut::trivial(n)]));
+    let mut m = Mapper {
+        g,
+        k,
+        refs: g.refs(),
+        cuts,
+        arrival: vec![0; g.len()],
+        flow: vec![0.0; g.len()],
+    };
+    m.pass(None);
+    if mode == Mode::Area {
+        let depth = m.depth();
+        let required = m.required(depth);
+        m.pass(Some(&required));
+        let required = m.required(depth);
+        m.exact(&required);
+    }
+    Mapping {
+        inputs: g.inputs,
+        luts: m
+            .cover()
+            .into_iter()
+            .map(|n| Lut {
+                output: n,
+                inputs: m.cuts[n][0].leaves.clone(),
+                truth: m.cuts[n][0].truth,
+            })
+            .collect(),
+        outputs: g.outputs.clone(),
+        depth: m.depth(),
+    }
+}
+
+impl Mapping {
+    fn net(&self, n: usize) -> String {
+        if n <= self.inputs {
+            format!("i{}", n - 1)
+        } else {
+            format!("n{}", n)
+        }
+    }
+
+    /// BLIF with a `.names` table per LUT
+    pub fn blif(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, ".model rbc").unwrap();
+        write!(out, ".inputs").unwrap();
+        for i in 0..self.inputs {
+            write!(out, " i{}", i).unwrap();
+        }
+        write!(out, "\n.outputs").unwrap();
+        for i in 0..self.outputs.len() {
+            write!(out, " o{}", i).unwrap();
+        }
+        writeln!(out).unwrap();
+        for lut in &self.luts {
+            write!(out, ".names").unwrap();
+            for i in &lut.inputs {
+                write!(out, " {}", self.net(*i)).unwrap();
+            }
+            writeln!(out, " {}", self.net(lut.output)).unwrap();
+            for m in 0..1_usize << lut.inputs.len() {
+                if (lut.truth >> m) & 1 == 1 {
+                    for j in 0..lut.inputs.len() {
+                        write!(out, "{}", (m >> j) & 1).unwrap();
+                    }
+                    writeln!(out, " 1").unwrap();
+                }
+            }
+        }
+        for (i, o) in self.outputs.iter().enumerate() {
+            match *o {
+                opt::FALSE => writeln!(out, ".names o{}", i).unwrap(),
+                opt::TRUE => writeln!(out, ".names o{}\n1", i).unwrap(),
+                _ => writeln!(
+                    out,
+                    ".names {} o{}\n{} 1",
+                    self.net(node(*o)),
+                    i,
+                    !inverted(*o) as u8
+                )
+                .unwrap(),
+            }
+        }
+        writeln!(out, ".end").unwrap();
+        out
+    }
+
+    /// Evaluate the LUTs, for checking them against the design
+    pub fn eval(&self, input: &[bool]) -> Vec<bool> {
+        let len = self
+            .luts
+            .iter()
+            .map(|l| l.output)
+            .chain(self.outputs.iter().map(|o| node(*o)))
+            .fold(self.inputs, usize::max);
+        let mut values = vec![false; len + 1];
+        values[1..=self.inputs].copy_from_slice(input);
+        for lut in &self.luts {
+            let index = lut
+                .inputs
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (j, i)| acc | ((values[*i] as usize) << j));
+            values[lut.output] = (lut.truth >> index) & 1 == 1;
+        }
+        self.outputs
+            .iter()
+            .map(|o| values[node(*o)] ^ inverted(*o))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{map, Mode};
+    use crate::opt::Graph;
+
+    #[test]
+    fn mapped() {
+        for file in [
+            &include_bytes!("../verilog/adder.pla")[..],
+            &include_bytes!("../verilog/popcount.pla")[..],
+        ] {
+            let (inputs, outputs) = crate::pla::parse(file).unwrap();
+            let g = Graph::from_aig(inputs, &outputs);
+            for k in [2, 3, 4] {
+                let depth = map(&g, k, Mode::Depth);
+                let area = map(&g, k, Mode::Area);
+                assert_eq!(depth.depth, area.depth);
+                assert!(area.luts.len() <= depth.luts.len());
+                for m in [depth, area] {
+                    for term in 0..1 << inputs {
+                        let input: Vec<_> = (0..inputs).map(|i| (term >> i) & 1 == 1).collect();
+                        let expected: Vec<_> = outputs.iter().map(|o| o.eval(&input)).collect();
+                        assert_eq!(m.eval(&input), expected);
+                    }
+                    for lut in &m.luts {
+                        assert!(lut.inputs.len() <= k);
+                    }
+                }
+            }
+            // 4 inputs fit a single 4-LUT per output
+            let m = map(&g, 4, Mode::Area);
+            assert_eq!(m.depth, 1);
+            assert!(m.luts.len() <= outputs.len());
+        }
+    }
+}
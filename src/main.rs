@@ -1,167 +1,615 @@
 use clap::Parser;
-use rbc::qmc::{reduce, Imp, ImpMax, Tri};
-use std::{collections::HashSet, fmt::Debug, ops::Sub};
+use rbc::aig::AIG;
+use rbc::factor::factor;
+#[cfg(feature = "parallel")]
+use rbc::qmc::par_reduce as reduce;
+#[cfg(not(feature = "parallel"))]
+use rbc::qmc::reduce;
+use rbc::qmc::{cover, indices, sorted, verify, Imp, ImpMax, Tri};
+use std::{
+    collections::HashSet,
+    fmt::{Debug, Display},
+};
 
 /// RBC: System for Combinational Logic Synthesis
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[arg(long, short)]
+    /// command to run, 1 to 29 (see --help)
+    ///
+    ///  1  canonical SOP               2  canonical POS
+    ///  3  canonical SOP of the inverse
+    ///  4  canonical POS of the inverse
+    ///  5  minimized SOP               6  minimized POS
+    ///  7  prime implicants and chart  8  essential primes and chart
+    ///  9  number of ON-Set minterms  10  number of ON-Set maxterms
+    /// 11  minimized SOP as a PLA     12  AIG of every output
+    /// 13  BDD size and minterm count 14  disjoint cubes of the BDD
+    /// 15  BDD size before and after reordering
+    /// 16  equivalence against a second design
+    /// 17  SAT and tautology checks   18  DIMACS CNF export
+    /// 19  AIG balancing              20  AIG rewriting
+    /// 21  factored form              22  technology mapping
+    /// 23  LUT mapping                24  size metrics
+    /// 25  symmetries                 26  unateness
+    /// 27  NPN classes                28  Karnaugh map
+    /// 29  QMC trace
+    #[arg(long, short, verbatim_doc_comment)]
     command: usize,
 
     #[arg(long, short)]
     expression: bool,
 
     file: String,
+
+    /// second design, for equivalence checking and miters
+    other: Option<String>,
+
+    /// output to show with commands 7, 8, 28 and 29, all outputs when unset,
+    /// and to export as DIMACS CNF with command 18, output 0 when unset
+    #[arg(long, short)]
+    output: Option<usize>,
+
+    /// genlib cell library for technology mapping, a built-in one when unset
+    #[arg(long, short)]
+    library: Option<String>,
+
+    /// number of LUT inputs for LUT mapping
+    #[arg(long, short = 'k', default_value_t = 6)]
+    lut_size: usize,
+
+    /// recover area after depth-optimal LUT mapping
+    #[arg(long)]
+    area: bool,
+
+    /// render Karnaugh maps as SVG instead of text
+    #[arg(long)]
+    svg: bool,
+
+    /// print canonical forms with named literals and in index notation
+    #[arg(long)]
+    algebraic: bool,
+
+    /// format of QMC traces and implicant charts
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// check minimized covers against the design, on by default in debug
+    /// builds, --verify=false turns it off
+    #[arg(long, num_args = 0..=1, require_equals = true, default_value_t = cfg!(debug_assertions), default_missing_value = "true", action = clap::ArgAction::Set)]
+    verify: bool,
 }
 
-fn main() {
-    let args = Args::parse();
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+fn fail(file: &str, e: impl Display) -> ! {
+    eprintln!("{}: {}", file, e);
+    std::process::exit(1)
+}
 
-    let buf = std::fs::read(args.file).unwrap();
+/// Read a design, as an expression when `expression` is set and by the
+/// file extension (.exp, .pla, .cnf, AIGER otherwise) if not
+fn load(file: &str, expression: bool) -> (usize, rbc::aag::Outputs) {
+    let buf = std::fs::read(file).unwrap_or_else(|e| fail(file, e));
 
-    let (inputs, outputs) = if !args.expression {
-        rbc::aag::parse(&buf).unwrap()
-    } else {
-        let e: Box<rbc::aig::AIG> = rbc::expr::calculator1::ExprParser::new()
-            .parse(&String::from_utf8(buf).unwrap())
-            .unwrap()
+    if expression || file.ends_with(".exp") {
+        let e: Box<AIG> = rbc::expr::calculator1::ExprParser::new()
+            .parse(&String::from_utf8_lossy(&buf))
+            .unwrap_or_else(|e| fail(file, e))
             .into();
         (e.syms(), vec![e])
-    };
+    } else if file.ends_with(".pla") {
+        rbc::pla::parse(&buf).unwrap_or_else(|e| fail(file, e))
+    } else if file.ends_with(".cnf") {
+        rbc::dimacs::parse(&buf).unwrap_or_else(|e| fail(file, e))
+    } else {
+        rbc::aag::parse(&buf).unwrap_or_else(|e| fail(file, e))
+    }
+}
 
-    for (i, output) in outputs.iter().enumerate() {
-        let mut minterms = HashSet::new();
-        let mut maxterms = HashSet::new();
-        let mut truthtab = vec![];
-
-        for term in 0..2_usize.pow(inputs as u32) {
-            let mut input = vec![];
-            let mut imp = vec![];
-            for i in 0..inputs {
-                input.push(((term >> i) & 1) == 1);
-                imp.push(if ((term >> i) & 1) == 1 {
-                    Tri::T
-                } else {
-                    Tri::F
-                });
-            }
-            let result = output.eval(&input);
-            truthtab.push((input, result));
-            if result {
-                minterms.insert(Imp(imp));
+/// Enumerate the ON-Set minterms and OFF-Set maxterms of an output over the
+/// inputs in `support`, the others being false
+fn truth_table(output: &AIG, inputs: usize, support: &[usize]) -> (HashSet<Imp>, HashSet<Imp>) {
+    let mut minterms = HashSet::new();
+    let mut maxterms = HashSet::new();
+
+    for term in 0..2_usize.pow(support.len() as u32) {
+        let mut input = vec![false; inputs];
+        let mut imp = vec![];
+        for (i, s) in support.iter().enumerate() {
+            input[*s] = ((term >> i) & 1) == 1;
+            imp.push(if ((term >> i) & 1) == 1 {
+                Tri::T
             } else {
-                maxterms.insert(Imp(imp));
+                Tri::F
+            });
+        }
+        if output.eval(&input) {
+            minterms.insert(Imp(imp));
+        } else {
+            maxterms.insert(Imp(imp));
+        }
+    }
+
+    (minterms, maxterms)
+}
+
+/// Map implicants over `support` back to all inputs
+fn widen(cover: HashSet<Imp>, support: &[usize], inputs: usize) -> HashSet<Imp> {
+    cover
+        .into_iter()
+        .map(|imp| {
+            let mut wide = vec![Tri::X; inputs];
+            for (t, s) in imp.0.into_iter().zip(support) {
+                wide[*s] = t;
             }
+            Imp(wide)
+        })
+        .collect()
+}
+
+/// Literals of the canonical form of `terms` over the inputs of `support`,
+/// over all inputs it would not fit a `usize` for wide designs
+fn canonical_literals(terms: &HashSet<Imp>, support: &[usize]) -> usize {
+    terms.len() * support.len()
+}
+
+/// Names of the inputs, from the symbols of the file when it has them and
+/// letters otherwise
+fn input_names(file: &str, expression: bool, inputs: usize) -> Vec<String> {
+    let mut names: Vec<String> = (0..inputs)
+        .map(
+            |i| match (expression || file.ends_with(".exp"), inputs <= 26) {
+                (true, _) => char::from_u32('A' as u32 + i as u32).unwrap().to_string(),
+                (false, true) => char::from_u32('a' as u32 + i as u32).unwrap().to_string(),
+                (false, false) => format!("x{}", i),
+            },
+        )
+        .collect();
+    let buf = std::fs::read(file).unwrap_or_else(|e| fail(file, e));
+    let symbols = if file.ends_with(".pla") {
+        rbc::pla::names(&buf)
+    } else if file.ends_with(".cnf") || file.ends_with(".exp") || expression {
+        vec![]
+    } else {
+        rbc::aag::names(&buf)
+    };
+    for (i, name) in symbols {
+        if i < inputs {
+            names[i] = name;
         }
+    }
+    names
+}
 
-        match args.command {
-            1 => {
-                // Return the design as a canonical SOP
+fn main() {
+    let args = Args::parse();
+    if !(1..=29).contains(&args.command) {
+        fail(
+            &args.file,
+            format!("unknown command {}, see --help", args.command),
+        );
+    }
+
+    let (inputs, outputs) = load(&args.file, args.expression);
+
+    if args.command == 16 {
+        // Check the design against a second one output by output with a miter
+        let other = args
+            .other
+            .as_deref()
+            .unwrap_or_else(|| fail(&args.file, "no second design to check against"));
+        let (other_inputs, other_outputs) = load(other, args.expression);
+        if outputs.len() != other_outputs.len() {
+            fail(other, "number of outputs differs");
+        }
+
+        // one CNF of the XOR of every output pair, solved output by output
+        let miters: Vec<Box<AIG>> = outputs
+            .iter()
+            .zip(&other_outputs)
+            .map(|(a, b)| a.clone() ^ b.clone())
+            .collect();
+        let inputs = std::cmp::max(inputs, other_inputs);
+        let mut cnf = rbc::sat::Cnf::new(inputs);
+        let lits = cnf.encode(&miters);
+        let mut solver = rbc::sat::Solver::from(&cnf);
+        let mut equivalent = true;
+        for (i, ((a, b), lit)) in outputs.iter().zip(&other_outputs).zip(&lits).enumerate() {
+            if !solver.solve(&[*lit]) {
+                println!("output {}: equivalent", i);
+                continue;
+            }
+            equivalent = false;
+            let input: Vec<bool> = (0..inputs).map(|v| solver.model(v)).collect();
+            println!(
+                "output {}: not equivalent, counterexample {}: {} vs {}",
+                i,
+                input
+                    .iter()
+                    .map(|v| if *v { "1" } else { "0" })
+                    .collect::<Vec<_>>()
+                    .join(""),
+                a.eval(&input) as u8,
+                b.eval(&input) as u8
+            );
+        }
+        if !equivalent {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.command == 15 {
+        // Report the shared BDD size of all outputs before and after reordering
+        let mut bdd = rbc::bdd::Manager::new(inputs);
+        let roots: Vec<_> = outputs.iter().map(|o| bdd.from_aig(o)).collect();
+        println!("BDD size in input order: {}", bdd.size(&roots));
+
+        let order = rbc::bdd::dfs_order(&outputs, inputs);
+        let mut bdd = rbc::bdd::Manager::with_order(&order);
+        let roots: Vec<_> = outputs.iter().map(|o| bdd.from_aig(o)).collect();
+        println!("BDD size in DFS order: {}", bdd.size(&roots));
+
+        println!("BDD size after sifting: {}", bdd.sift(&roots));
+        println!(
+            "BDD order after sifting: {}",
+            bdd.order()
+                .iter()
+                .map(|v| format!("i{}", v))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        return;
+    }
+
+    if args.command == 27 {
+        // Group outputs, of this design and of a second one, by NPN class over their supports
+        let mut designs = vec![("output", inputs, outputs.clone())];
+        if let Some(other) = args.other.as_deref() {
+            let (other_inputs, other_outputs) = load(other, args.expression);
+            designs.push(("other output", other_inputs, other_outputs));
+        }
+        let mut classes: Vec<(rbc::truth::Truth, Vec<String>)> = vec![];
+        for (label, inputs, outputs) in designs {
+            let mut bdd = rbc::bdd::Manager::new(inputs);
+            for (i, output) in outputs.iter().enumerate() {
+                let f = bdd.from_aig(output);
+                let support = bdd.support(f);
+                if support.len() > rbc::truth::NPN_VARS {
+                    fail(
+                        &args.file,
+                        format!(
+                            "{} {} depends on {} inputs, NPN classes need at most {}",
+                            label,
+                            i,
+                            support.len(),
+                            rbc::truth::NPN_VARS
+                        ),
+                    );
+                }
+                let t = rbc::truth::Truth::new(support.len(), |x| {
+                    let mut input = vec![false; inputs];
+                    for (v, s) in x.iter().zip(&support) {
+                        input[*s] = *v;
+                    }
+                    output.eval(&input)
+                });
+                let (canonical, npn) = t.npn();
                 println!(
-                    "canonical SOP of output {}: {}",
+                    "NPN class of {} {}: {} over {} inputs, output {}, inputs {}",
+                    label,
                     i,
-                    minterms
+                    canonical,
+                    support.len(),
+                    if npn.output { "negated" } else { "kept" },
+                    npn.perm
                         .iter()
-                        .map(Imp::to_string)
+                        .map(|p| format!(
+                            "{}{}",
+                            if npn.phases[*p] { "!" } else { "" },
+                            support[*p]
+                        ))
                         .collect::<Vec<_>>()
-                        .join(" + ")
+                        .join(" ")
                 );
+                let name = format!("{} {}", label, i);
+                match classes.iter_mut().find(|(c, _)| *c == canonical) {
+                    Some((_, members)) => members.push(name),
+                    None => classes.push((canonical, vec![name])),
+                }
+            }
+        }
+        println!(
+            "NPN classes: {}",
+            classes
+                .iter()
+                .map(|(_, m)| format!("{{{}}}", m.join(", ")))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        return;
+    }
+
+    if args.command == 24 {
+        // Report size metrics of the design and of every output
+        let graph = rbc::opt::Graph::from_aig(inputs, &outputs);
+        println!(
+            "design: {} inputs, {} outputs, {}",
+            inputs,
+            outputs.len(),
+            rbc::stats::stats(&graph, &graph.outputs)
+        );
+        for (i, o) in graph.outputs.iter().enumerate() {
+            print!("output {}: {}", i, rbc::stats::stats(&graph, &[*o]));
+            if *o == rbc::opt::FALSE || *o == rbc::opt::TRUE {
+                print!(", constant {}", o);
+            }
+            if let Some(j) = graph.outputs[..i].iter().position(|p| p == o) {
+                print!(", duplicate of output {}", j);
+            } else if let Some(j) = graph.outputs[..i].iter().position(|p| *p == o ^ 1) {
+                print!(", complement of output {}", j);
+            }
+            println!();
+        }
+        return;
+    }
+
+    if args.command == 23 {
+        // Map the design onto K-input LUTs and report their number and depth
+        if !(2..=6).contains(&args.lut_size) {
+            fail(&args.file, "LUT size must be between 2 and 6");
+        }
+        let mode = if args.area {
+            rbc::lut::Mode::Area
+        } else {
+            rbc::lut::Mode::Depth
+        };
+        let graph = rbc::opt::Graph::from_aig(inputs, &outputs);
+        let mapping = rbc::lut::map(&graph, args.lut_size, mode);
+        print!("{}", mapping.blif());
+        println!(
+            "mapped {}-LUTs: {} LUTs, depth {}",
+            args.lut_size,
+            mapping.luts.len(),
+            mapping.depth
+        );
+        return;
+    }
+
+    if args.command == 22 {
+        // Map the design onto a cell library and report area and delay
+        let lib = match args.library.as_deref() {
+            Some(file) => {
+                let buf = std::fs::read(file).unwrap_or_else(|e| fail(file, e));
+                rbc::map::Library::parse(&buf).unwrap_or_else(|e| fail(file, e))
+            }
+            None => rbc::map::Library::parse(rbc::map::GENLIB.as_bytes()).unwrap(),
+        };
+        let graph = rbc::opt::Graph::from_aig(inputs, &outputs);
+        let netlist = rbc::map::map(&graph, &lib);
+        print!("{}", netlist.blif(&lib));
+        println!(
+            "mapped netlist: {} cells, area {}, delay {}",
+            netlist.cells.len(),
+            netlist.area,
+            netlist.delay
+        );
+        return;
+    }
+
+    if args.command == 20 {
+        // Rewrite 4-input cuts and report depth and AND nodes before and after
+        let graph = rbc::opt::Graph::from_aig(inputs, &outputs);
+        let rewritten = rbc::opt::rewrite(&graph);
+        println!(
+            "AIG before rewriting: depth {}, {} AND nodes",
+            graph.depth(),
+            graph.ands()
+        );
+        println!(
+            "AIG after rewriting: depth {}, {} AND nodes",
+            rewritten.depth(),
+            rewritten.ands()
+        );
+        for (i, output) in rewritten.to_aig().iter().enumerate() {
+            println!("rewritten AIG of output {}: {}", i, output);
+        }
+        return;
+    }
+
+    if args.command == 19 {
+        // Balance the AIG and report depth and AND nodes before and after
+        let balanced = rbc::aig::balance(&outputs);
+        println!(
+            "AIG before balancing: depth {}, {} AND nodes",
+            rbc::aig::depth(&outputs),
+            rbc::aig::ands(&outputs)
+        );
+        println!(
+            "AIG after balancing: depth {}, {} AND nodes",
+            rbc::aig::depth(&balanced),
+            rbc::aig::ands(&balanced)
+        );
+        for (i, output) in balanced.iter().enumerate() {
+            println!("balanced AIG of output {}: {}", i, output);
+        }
+        return;
+    }
+
+    if args.command == 18 {
+        // Write DIMACS CNF of one output, 0 unless -o is given, or of the
+        // miter against a second design
+        let (inputs, root) = match args.other.as_deref() {
+            Some(other) => {
+                let (other_inputs, other_outputs) = load(other, args.expression);
+                if outputs.len() != other_outputs.len() {
+                    fail(other, "number of outputs differs");
+                }
+                (
+                    std::cmp::max(inputs, other_inputs),
+                    rbc::aig::miter(&outputs, &other_outputs),
+                )
+            }
+            None => match outputs.get(args.output.unwrap_or(0)) {
+                Some(output) => (inputs, output.clone()),
+                None => fail(&args.file, "no such output"),
+            },
+        };
+        let mut cnf = rbc::sat::Cnf::new(inputs);
+        let root = cnf.encode(&[root])[0];
+        print!("{}", rbc::dimacs::write(&cnf, inputs, root));
+        return;
+    }
+
+    if args.command == 17 {
+        // Decide satisfiability and tautology of every output with SAT
+        let mut cnf = rbc::sat::Cnf::new(inputs);
+        let lits = cnf.encode(&outputs);
+        let mut solver = rbc::sat::Solver::from(&cnf);
+        let witness = |solver: &rbc::sat::Solver| {
+            (0..inputs)
+                .map(|v| if solver.model(v) { "1" } else { "0" })
+                .collect::<Vec<_>>()
+                .join("")
+        };
+        for (i, lit) in lits.iter().enumerate() {
+            let sat = if solver.solve(&[*lit]) {
+                format!("satisfiable by {}", witness(&solver))
+            } else {
+                "unsatisfiable".to_string()
+            };
+            let taut = if solver.solve(&[!*lit]) {
+                format!("falsified by {}", witness(&solver))
+            } else {
+                "tautology".to_string()
+            };
+            println!("SAT of output {}: {}, {}", i, sat, taut);
+        }
+        return;
+    }
+
+    let mut bdd = rbc::bdd::Manager::new(inputs);
+    let mut roots = vec![];
+
+    // factored forms and K-maps always name their inputs, canonical forms
+    // only when algebraic, followed by their index notation
+    let names = if args.algebraic || matches!(args.command, 21 | 28) {
+        input_names(&args.file, args.expression, inputs)
+    } else {
+        vec![]
+    };
+    let sop = |terms: &HashSet<Imp>| {
+        let cubes = sorted(terms);
+        if !args.algebraic {
+            return cubes
+                .iter()
+                .map(Imp::to_string)
+                .collect::<Vec<_>>()
+                .join(" + ");
+        }
+        let products: Vec<String> = cubes.iter().map(|c| c.product(&names)).collect();
+        format!(
+            "{} = {}",
+            if products.is_empty() {
+                "0".to_string()
+            } else {
+                products.join(" + ")
+            },
+            indices(terms, false)
+        )
+    };
+    let pos = |terms: &HashSet<Imp>| {
+        let clauses = sorted(terms).into_iter().map(ImpMax::from);
+        if !args.algebraic {
+            return clauses.map(|c| c.to_string()).collect::<Vec<_>>().join("");
+        }
+        let sums: Vec<String> = clauses.map(|c| c.sum(&names)).collect();
+        format!(
+            "{} = {}",
+            if sums.is_empty() {
+                "1".to_string()
+            } else {
+                sums.join("")
+            },
+            indices(terms, true)
+        )
+    };
+
+    for (i, output) in outputs.iter().enumerate() {
+        // minimization only enumerates the inputs the output depends on
+        let support: Vec<usize> = match args.command {
+            5..=8 | 11 | 21 => {
+                let f = bdd.from_aig(output);
+                bdd.support(f)
+            }
+            _ => (0..inputs).collect(),
+        };
+        let (minterms, maxterms) = match args.command {
+            1..=11 | 21 | 25 | 26 | 28 | 29 => truth_table(output, inputs, &support),
+            _ => Default::default(),
+        };
+
+        // check a printed cover of the ON-Set, or of the OFF-Set when `on` is
+        // unset, over the support before it is widened to all inputs
+        let check = |chosen: &HashSet<Imp>, on: bool| {
+            if args.verify {
+                verify(chosen, support.len(), |x| {
+                    let mut input = vec![false; inputs];
+                    for (v, s) in x.iter().zip(&support) {
+                        input[*s] = *v;
+                    }
+                    output.eval(&input) == on
+                })
+                .unwrap_or_else(|e| fail(&args.file, format!("output {}: {}", i, e)));
+            }
+        };
+
+        match args.command {
+            1 => {
+                // Return the design as a canonical SOP
+                println!("canonical SOP of output {}: {}", i, sop(&minterms));
             }
             2 => {
                 // Return the design as a canonical POS
-                println!(
-                    "canonical POS of output {}: {}",
-                    i,
-                    maxterms
-                        .clone()
-                        .into_iter()
-                        .map(ImpMax::from)
-                        .collect::<Vec<_>>()
-                        .iter()
-                        .map(ImpMax::to_string)
-                        .collect::<Vec<_>>()
-                        .join("")
-                );
+                println!("canonical POS of output {}: {}", i, pos(&maxterms));
             }
             3 => {
                 // Return the design INVERSE as a canonical SOP
-                println!(
-                    "canonical SOP of output {} INVERSE: {}",
-                    i,
-                    maxterms
-                        .iter()
-                        .map(Imp::to_string)
-                        .collect::<Vec<_>>()
-                        .join(" + ")
-                );
+                println!("canonical SOP of output {} INVERSE: {}", i, sop(&maxterms));
             }
             4 => {
                 // Return the design INVERSE as a canonical POS
-                println!(
-                    "canonical POS of output {} INVERSE: {}",
-                    i,
-                    minterms
-                        .clone()
-                        .into_iter()
-                        .map(ImpMax::from)
-                        .collect::<Vec<_>>()
-                        .iter()
-                        .map(ImpMax::to_string)
-                        .collect::<Vec<_>>()
-                        .join("")
-                );
+                println!("canonical POS of output {} INVERSE: {}", i, pos(&minterms));
             }
             5 | 11 => {
                 // Return a minimized number of literals representation in SOP
                 // Report on the number of saved literals vs. the canonical version
 
-                let mut columns = minterms.clone();
-                let rows = reduce(&columns);
-
-                let mut chosen: HashSet<Imp> = HashSet::new();
-
-                let mut fallback = false;
-
-                loop {
-                    let mut covered: HashSet<Imp> = HashSet::new();
-
-                    for col in &columns {
-                        let cover: Vec<_> = rows.iter().filter(|p| p.containes(col)).collect();
-                        if cover.len() == 1 || fallback {
-                            fallback = false;
-                            chosen.insert(cover[0].clone());
-                            for col in &columns {
-                                if cover[0].containes(col) {
-                                    covered.insert(col.clone());
-                                }
-                            }
-                        }
-                    }
-
-                    columns = columns.sub(&covered);
-
-                    if columns.is_empty() {
-                        break;
-                    }
-
-                    fallback = covered.is_empty();
-                }
+                let chosen = cover(&minterms, &reduce(&minterms));
+                check(&chosen, true);
+                let chosen = widen(chosen, &support, inputs);
 
                 if args.command == 5 {
                     println!(
-                        "minimized SOP of output {}: {}, saved {} literals",
+                        "minimized SOP of output {}: {}, saved {} literals over the support, {} literals factored",
                         i,
-                        chosen
+                        sorted(&chosen)
                             .iter()
                             .map(Imp::to_string)
                             .collect::<Vec<_>>()
                             .join(" + "),
-                        minterms.iter().map(Imp::literals).sum::<usize>()
-                            - chosen.iter().map(Imp::literals).sum::<usize>()
+                        canonical_literals(&minterms, &support)
+                            - chosen.iter().map(Imp::literals).sum::<usize>(),
+                        factor(&chosen).literals()
                     );
                 } else {
-                    for term in chosen {
+                    for term in sorted(&chosen) {
                         println!(
                             "{} {}",
                             term,
@@ -177,43 +625,14 @@ fn main() {
                 // Return a minimized number of literals representation in POS
                 // Report on the number of saved literals vs. the canonical version
 
-                let mut columns = maxterms.clone();
-                let rows = reduce(&columns);
-
-                let mut chosen: HashSet<Imp> = HashSet::new();
-
-                let mut fallback = false;
-
-                loop {
-                    let mut covered: HashSet<Imp> = HashSet::new();
-
-                    for col in &columns {
-                        let cover: Vec<_> = rows.iter().filter(|p| p.containes(col)).collect();
-                        if cover.len() == 1 || fallback {
-                            fallback = false;
-                            chosen.insert(cover[0].clone());
-                            for col in &columns {
-                                if cover[0].containes(col) {
-                                    covered.insert(col.clone());
-                                }
-                            }
-                        }
-                    }
-
-                    columns = columns.sub(&covered);
-
-                    if columns.is_empty() {
-                        break;
-                    }
-
-                    fallback = covered.is_empty();
-                }
+                let chosen = cover(&maxterms, &reduce(&maxterms));
+                check(&chosen, false);
+                let chosen = widen(chosen, &support, inputs);
 
                 println!(
-                    "minimized POS of output {}: {}, saved {} literals",
+                    "minimized POS of output {}: {}, saved {} literals over the support, {} literals factored",
                     i,
-                    chosen
-                        .clone()
+                    sorted(&chosen)
                         .into_iter()
                         .map(ImpMax::from)
                         .collect::<Vec<_>>()
@@ -221,33 +640,50 @@ fn main() {
                         .map(ImpMax::to_string)
                         .collect::<Vec<_>>()
                         .join(" + "),
-                    maxterms.iter().map(Imp::literals).sum::<usize>()
-                        - chosen.iter().map(Imp::literals).sum::<usize>()
+                    canonical_literals(&maxterms, &support)
+                        - chosen.iter().map(Imp::literals).sum::<usize>(),
+                    factor(&chosen).literals()
                 );
             }
-            7 => {
-                // Report the number of Prime Implicants
-                println!(
-                    "number of Prime Implicants of output {}: {}",
-                    i,
-                    reduce(&minterms).len()
-                );
-            }
-            8 => {
-                // Report the number of Essential Prime Implicants
-                let mut ess = HashSet::new();
-                let prime = reduce(&minterms);
-                for m in &minterms {
-                    let primes: Vec<_> = prime.iter().filter(|p| p.containes(m)).collect();
-                    if primes.len() == 1 {
-                        ess.insert(primes[0]);
+            7 | 8 => {
+                // Report the Prime Implicants or the Essential Prime
+                // Implicants with the implicant chart
+                if args.output.is_some_and(|o| o != i) {
+                    continue;
+                }
+                // the chart columns are the minterms over the support
+                let chart = rbc::trace::Chart::new(&minterms, &reduce(&minterms), &support);
+                let (name, listed) = if args.command == 7 {
+                    ("Prime Implicants", chart.primes.iter().collect())
+                } else {
+                    ("Essential Prime Implicants", chart.essentials())
+                };
+                match args.format {
+                    Format::Text => {
+                        println!("number of {} of output {}: {}", name, i, listed.len());
+                        for imp in &listed {
+                            println!("  {}", imp);
+                        }
+                        println!("implicant chart of output {}:", i);
+                        print!("{}", chart.text());
                     }
+                    Format::Csv => print!("{}", chart.csv()),
+                    Format::Json => println!(
+                        "{{\"output\":{},\"{}\":[{}],\"chart\":{}}}",
+                        i,
+                        if args.command == 7 {
+                            "primes"
+                        } else {
+                            "essentials"
+                        },
+                        listed
+                            .iter()
+                            .map(|imp| format!("\"{}\"", imp))
+                            .collect::<Vec<_>>()
+                            .join(","),
+                        chart.json()
+                    ),
                 }
-                println!(
-                    "number of Essential Prime Implicants of output {}: {}",
-                    i,
-                    ess.len()
-                );
             }
             9 => {
                 // Report the number of ON-Set minterms
@@ -269,7 +705,134 @@ fn main() {
                 // Command of your choice #2
                 println!("AIG of output {}: {}", i, output);
             }
-            _ => unimplemented!(),
+            13 => {
+                // Report the BDD size and the number of ON-Set minterms without enumeration
+                let f = bdd.from_aig(output);
+                print!(
+                    "BDD of output {}: {} nodes, {} ON-Set minterms",
+                    i,
+                    bdd.size(&[f]),
+                    bdd.sat_count(f)
+                );
+                match roots.iter().position(|g| *g == f) {
+                    Some(j) => println!(", equivalent to output {}", j),
+                    None => println!(),
+                }
+                roots.push(f);
+            }
+            14 => {
+                // Return the design as disjoint cubes of the BDD paths
+                let f = bdd.from_aig(output);
+                println!(
+                    "BDD cubes of output {}: {}",
+                    i,
+                    bdd.cubes(f)
+                        .iter()
+                        .map(Imp::to_string)
+                        .collect::<Vec<_>>()
+                        .join(" + ")
+                );
+            }
+            21 => {
+                // Return the minimized SOP in factored form
+                let chosen = cover(&minterms, &reduce(&minterms));
+                check(&chosen, true);
+                let chosen = widen(chosen, &support, inputs);
+                let factored = factor(&chosen);
+                println!(
+                    "factored form of output {}: {}, {} literals, {} in SOP",
+                    i,
+                    factored.named(&names),
+                    factored.literals(),
+                    chosen.iter().map(Imp::literals).sum::<usize>()
+                );
+            }
+            25 => {
+                // Report classes of symmetric inputs and antisymmetric pairs
+                let t = rbc::truth::Truth::from_imps(inputs, &minterms);
+                let classes: Vec<String> = t
+                    .symmetry_classes()
+                    .into_iter()
+                    .filter(|c| c.len() > 1 && t.depends(c[0]))
+                    .map(|c| {
+                        format!(
+                            "{{{}}}",
+                            c.iter().map(usize::to_string).collect::<Vec<_>>().join(" ")
+                        )
+                    })
+                    .collect();
+                let mut pairs = vec![];
+                for a in (0..inputs).filter(|a| t.depends(*a)) {
+                    for b in (a + 1..inputs).filter(|b| t.depends(*b)) {
+                        if t.antisymmetric(a, b) {
+                            pairs.push(format!("({} {})", a, b));
+                        }
+                    }
+                }
+                println!(
+                    "symmetries of output {}: classes {}, antisymmetric pairs {}",
+                    i,
+                    if classes.is_empty() {
+                        "none".to_string()
+                    } else {
+                        classes.join(" ")
+                    },
+                    if pairs.is_empty() {
+                        "none".to_string()
+                    } else {
+                        pairs.join(" ")
+                    }
+                );
+            }
+            26 => {
+                // Classify every input as positive or negative unate, binate or independent
+                let t = rbc::truth::Truth::from_imps(inputs, &minterms);
+                println!(
+                    "unateness of output {}: {}",
+                    i,
+                    (0..inputs)
+                        .map(|v| format!("{} {}", v, t.unateness(v)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            28 => {
+                // Render a Karnaugh map with the chosen primes as groups
+                if !(2..=6).contains(&inputs) {
+                    fail(&args.file, "Karnaugh maps need 2 to 6 inputs");
+                }
+                if args.output.is_some_and(|o| o != i) {
+                    continue;
+                }
+                let t = rbc::truth::Truth::from_imps(inputs, &minterms);
+                let chosen = cover(&minterms, &reduce(&minterms));
+                check(&chosen, true);
+                let chosen = sorted(&chosen);
+                let map = rbc::kmap::Kmap::new(&t, &chosen, &names);
+                if args.svg {
+                    print!("{}", map.svg());
+                } else {
+                    println!("Karnaugh map of output {}:", i);
+                    print!("{}", map.ascii());
+                }
+            }
+            29 => {
+                // Trace the merge rounds and covering steps of QMC
+                if args.output.is_some_and(|o| o != i) {
+                    continue;
+                }
+                let trace = rbc::trace::trace(&minterms);
+                check(&trace.steps.iter().map(|s| s.prime.clone()).collect(), true);
+                match args.format {
+                    Format::Text => {
+                        println!("QMC trace of output {}:", i);
+                        print!("{}", trace.text());
+                    }
+                    Format::Json => println!("{}", trace.json()),
+                    Format::Csv => fail(&args.file, "QMC traces are text or JSON"),
+                }
+            }
+            _ => unreachable!(),
         }
     }
 }
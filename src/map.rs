@@ -0,0 +1,628 @@
+use crate::opt::{self, cuts, inverted, node, Graph, Lit, VARS};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while1},
+    character::complete::{char, multispace1, not_line_ending, space0},
+    combinator::{self, value},
+    multi::{many0, many1},
+    number::complete::double,
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    Finish, IResult,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Write},
+};
+
+/// Library used when none is given, areas and delays in units of an inverter
+pub const GENLIB: &str = "\
+GATE ZERO  0 O=CONST0;
+GATE ONE   0 O=CONST1;
+GATE INV   1 O=!a;             PIN * INV    1 999 1.0 0 1.0 0
+GATE NAND2 2 O=!(a*b);         PIN * INV    1 999 1.0 0 1.0 0
+GATE NOR2  2 O=!(a+b);         PIN * INV    1 999 1.4 0 1.4 0
+GATE AND2  3 O=a*b;            PIN * NONINV 1 999 1.6 0 1.6 0
+GATE OR2   3 O=a+b;            PIN * NONINV 1 999 1.8 0 1.8 0
+GATE AOI21 3 O=!(a*b+c);       PIN * INV    1 999 1.6 0 1.6 0
+GATE OAI21 3 O=!((a+b)*c);     PIN * INV    1 999 1.6 0 1.6 0
+GATE AOI22 4 O=!(a*b+c*d);     PIN * INV    1 999 2.0 0 2.0 0
+GATE OAI22 4 O=!((a+b)*(c+d)); PIN * INV    1 999 2.0 0 2.0 0
+GATE XOR2  5 O=a*!b+!a*b;      PIN * UNKNOWN 2 999 2.0 0 2.0 0
+GATE XNOR2 5 O=a*b+!a*!b;      PIN * UNKNOWN 2 999 2.0 0 2.0 0
+";
+
+/// Largest number of pins of a gate
+const PINS: usize = 6;
+
+#[derive(Debug, Clone)]
+enum Func<'a> {
+    Const(bool),
+    Pin(&'a [u8]),
+    Not(Box<Func<'a>>),
+    And(Box<Func<'a>>, Box<Func<'a>>),
+    Or(Box<Func<'a>>, Box<Func<'a>>),
+}
+
+impl<'a> Func<'a> {
+    fn pins(&self, pins: &mut Vec<&'a [u8]>) {
+        match self {
+            Func::Const(_) => (),
+            Func::Pin(p) => {
+                if !pins.contains(p) {
+                    pins.push(p)
+                }
+            }
+            Func::Not(f) => f.pins(pins),
+            Func::And(l, r) | Func::Or(l, r) => {
+                l.pins(pins);
+                r.pins(pins);
+            }
+        }
+    }
+
+    fn truth(&self, pins: &[&[u8]]) -> u64 {
+        match self {
+            Func::Const(v) => 0_u64.wrapping_sub(*v as u64),
+            Func::Pin(p) => VARS[pins.iter().position(|x| x == p).unwrap()],
+            Func::Not(f) => !f.truth(pins),
+            Func::And(l, r) => l.truth(pins) & r.truth(pins),
+            Func::Or(l, r) => l.truth(pins) | r.truth(pins),
+        }
+    }
+}
+
+/// whitespace and comments
+fn skip(input: &[u8]) -> IResult<&[u8], ()> {
+    value(
+        (),
+        many0(alt((
+            value((), multispace1),
+            value((), pair(char('#'), not_line_ending)),
+        ))),
+    )(input)
+}
+
+fn ident(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    take_while1(|c: u8| {
+        c.is_ascii_alphanumeric() || c == b'_' || c == b'.' || c == b'[' || c == b']'
+    })(input)
+}
+
+fn word(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    take_while1(|c: u8| !c.is_ascii_whitespace())(input)
+}
+
+fn primary(input: &[u8]) -> IResult<&[u8], Func<'_>> {
+    let (input, f) = delimited(
+        space0,
+        alt((
+            delimited(char('('), sum, char(')')),
+            value(Func::Const(false), tag(b"CONST0")),
+            value(Func::Const(true), tag(b"CONST1")),
+            combinator::map(ident, Func::Pin),
+        )),
+        space0,
+    )(input)?;
+    // postfix complement
+    let (input, primes) = many0(terminated(char('\''), space0))(input)?;
+    Ok((input, primes.iter().fold(f, |f, _| Func::Not(Box::new(f)))))
+}
+
+fn unary(input: &[u8]) -> IResult<&[u8], Func<'_>> {
+    alt((
+        combinator::map(preceded(pair(space0, char('!')), unary), |f| {
+            Func::Not(Box::new(f))
+        }),
+        primary,
+    ))(input)
+}
+
+fn product(input: &[u8]) -> IResult<&[u8], Func<'_>> {
+    let (input, first) = unary(input)?;
+    let (input, rest) = many0(preceded(alt((char('*'), char('&'))), unary))(input)?;
+    Ok((
+        input,
+        rest.into_iter()
+            .fold(first, |l, r| Func::And(Box::new(l), Box::new(r))),
+    ))
+}
+
+fn sum(input: &[u8]) -> IResult<&[u8], Func<'_>> {
+    let (input, first) = product(input)?;
+    let (input, rest) = many0(preceded(alt((char('+'), char('|'))), product))(input)?;
+    Ok((
+        input,
+        rest.into_iter()
+            .fold(first, |l, r| Func::Or(Box::new(l), Box::new(r))),
+    ))
+}
+
+enum Item<'a> {
+    /// name, area, output and function
+    Gate(&'a [u8], f64, &'a [u8], Func<'a>),
+    /// pin name and its block delay
+    Pin(&'a [u8], f64),
+}
+
+fn gate(input: &[u8]) -> IResult<&[u8], Item<'_>> {
+    combinator::map(
+        tuple((
+            tag(b"GATE"),
+            preceded(skip, word),
+            preceded(skip, double),
+            preceded(skip, ident),
+            preceded(space0, char('=')),
+            sum,
+            char(';'),
+        )),
+        |(_, name, area, output, _, func, _)| Item::Gate(name, area, output, func),
+    )(input)
+}
+
+fn pin(input: &[u8]) -> IResult<&[u8], Item<'_>> {
+    combinator::map(
+        tuple((
+            tag(b"PIN"),
+            preceded(skip, alt((tag(b"*"), ident))),
+            preceded(skip, word),
+            preceded(skip, double),
+            preceded(skip, double),
+            preceded(skip, double),
+            preceded(skip, double),
+            preceded(skip, double),
+            preceded(skip, double),
+        )),
+        |(_, name, _, _, _, rise, _, fall, _)| Item::Pin(name, rise.max(fall)),
+    )(input)
+}
+
+fn items(input: &[u8]) -> IResult<&[u8], Vec<Item<'_>>> {
+    terminated(many1(preceded(skip, alt((gate, pin)))), skip)(input)
+}
+
+#[derive(Debug)]
+pub enum Error<'a> {
+    /// malformed genlib text
+    Syntax(nom::error::Error<&'a [u8]>),
+    /// gate with more pins than supported
+    Pins(String),
+    /// library without an inverter and a 2-input AND, NAND, OR or NOR
+    Incomplete,
+}
+
+impl<'a> From<nom::error::Error<&'a [u8]>> for Error<'a> {
+    fn from(value: nom::error::Error<&'a [u8]>) -> Self {
+        Self::Syntax(value)
+    }
+}
+
+impl Display for Error<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Syntax(e) => write!(
+                f,
+                "syntax error: {:?} at {:?}",
+                e.code,
+                String::from_utf8_lossy(e.input)
+            ),
+            Error::Pins(g) => write!(f, "gate {} has more than {} pins", g, PINS),
+            Error::Incomplete => write!(f, "library cannot implement every AND node"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Gate {
+    pub name: String,
+    pub area: f64,
+    pub output: String,
+    pub pins: Vec<String>,
+    /// block delay from every pin to the output
+    pub delays: Vec<f64>,
+    /// function over the pins, pin `i` being variable `i`
+    pub truth: u64,
+}
+
+/// gate and the cut leaf of every pin
+type Match = (usize, Vec<usize>);
+
+/// Gates of a genlib file, indexed by their functions under every pin
+/// permutation
+#[derive(Debug, Clone)]
+pub struct Library {
+    pub gates: Vec<Gate>,
+    /// matching gates by the number of inputs and the function of a cut
+    matches: HashMap<(usize, u64), Vec<Match>>,
+    inverter: usize,
+}
+
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![vec![]];
+    }
+    let mut result = vec![];
+    for p in permutations(n - 1) {
+        for i in 0..n {
+            let mut p = p.clone();
+            p.insert(i, n - 1);
+            result.push(p);
+        }
+    }
+    result
+}
+
+/// truth table with pin `i` connected to variable `perm[i]`
+fn permute(truth: u64, perm: &[usize]) -> u64 {
+    let mut result = 0;
+    for m in 0..64 {
+        let index = perm
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (i, p)| acc | (((m >> p) & 1) << i));
+        result |= ((truth >> index) & 1) << m;
+    }
+    result
+}
+
+/// truth table with variable `i` complemented
+fn flip(truth: u64, i: usize) -> u64 {
+    let s = 1 << i;
+    ((truth & VARS[i]) >> s) | ((truth & !VARS[i]) << s)
+}
+
+impl Library {
+    pub fn parse(input: &[u8]) -> Result<Self, Error<'_>> {
+        let (_, items) = combinator::all_consuming(items)(input).finish()?;
+        let mut gates: Vec<Gate> = vec![];
+        for item in items {
+            match item {
+                Item::Gate(name, area, output, func) => {
+                    let mut pins = vec![];
+                    func.pins(&mut pins);
+                    let name = String::from_utf8_lossy(name).to_string();
+                    if pins.len() > PINS {
+                        return Err(Error::Pins(name));
+                    }
+                    gates.push(Gate {
+                        name,
+                        area,
+                        output: String::from_utf8_lossy(output).to_string(),
+                        delays: vec![1.0; pins.len()],
+                        truth: func.truth(&pins),
+                        pins: pins
+                            .iter()
+                            .map(|p| String::from_utf8_lossy(p).to_string())
+                            .collect(),
+                    });
+                }
+                Item::Pin(name, delay) => {
+                    if let Some(g) = gates.last_mut() {
+                        for (p, d) in g.pins.iter().zip(g.delays.iter_mut()) {
+                            if name == b"*" || p.as_bytes() == name {
+                                *d = delay;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Self::new(gates)
+    }
+
+    fn new<'a>(gates: Vec<Gate>) -> Result<Self, Error<'a>> {
+        let mut matches: HashMap<(usize, u64), Vec<Match>> = HashMap::new();
+        for (i, g) in gates.iter().enumerate() {
+            if g.pins.is_empty() {
+                continue;
+            }
+            let mut seen = HashSet::new();
+            for perm in permutations(g.pins.len()) {
+                let truth = permute(g.truth, &perm);
+                if seen.insert(truth) {
+                    matches
+                        .entry((g.pins.len(), truth))
+                        .or_default()
+                        .push((i, perm));
+                }
+            }
+        }
+        let inverter = gates
+            .iter()
+            .enumerate()
+            .filter(|(_, g)| g.pins.len() == 1 && g.truth == !VARS[0])
+            .min_by(|(_, a), (_, b)| a.area.total_cmp(&b.area))
+            .map(|(i, _)| i)
+            .ok_or(Error::Incomplete)?;
+        let and = VARS[0] & VARS[1];
+        if ![and, !and, VARS[0] | VARS[1], !(VARS[0] | VARS[1])]
+            .iter()
+            .any(|t| matches.contains_key(&(2, *t)))
+        {
+            return Err(Error::Incomplete);
+        }
+        Ok(Self {
+            gates,
+            matches,
+            inverter,
+        })
+    }
+}
+
+/// Gate driving `output` from `inputs`, in the order of the gate pins
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    pub gate: usize,
+    pub inputs: Vec<Lit>,
+    pub output: Lit,
+}
+
+#[derive(Debug, Clone)]
+pub struct Netlist {
+    pub inputs: usize,
+    pub cells: Vec<Cell>,
+    pub outputs: Vec<Lit>,
+    pub area: f64,
+    pub delay: f64,
+}
+
+#[derive(Debug, Clone)]
+struct Choice {
+    gate: usize,
+    inputs: Vec<Lit>,
+    arrival: f64,
+    flow: f64,
+}
+
+impl Choice {
+    fn better(&self, other: &Option<Choice>) -> bool {
+        match other {
+            None => true,
+            Some(o) => (self.flow, self.arrival) < (o.flow, o.arrival),
+        }
+    }
+}
+
+/// Map every AND node in both phases to the gate of one of its cuts with the
+/// least area flow, then delay, and cover the outputs with the choices
+pub fn map(g: &Graph, lib: &Library) -> Netlist {
+    let k = lib.gates.iter().map(|g| g.pins.len()).max().unwrap_or(2);
+    let cuts = cuts(g, k, 8);
+    let refs = g.refs();
+    // per literal, `None` for the constant and positive inputs
+    let mut best: Vec<Option<Choice>> = vec![None; 2 * g.len()];
+    let mut arrival = vec![0.0; 2 * g.len()];
+    let mut flow = vec![0.0; 2 * g.len()];
+
+    let inv = &lib.gates[lib.inverter];
+    let inverter = |l: Lit, arrival: &[f64], flow: &[f64]| Choice {
+        gate: lib.inverter,
+        inputs: vec![l],
+        arrival: arrival[l as usize] + inv.delays[0],
+        flow: inv.area + flow[l as usize],
+    };
+
+    for (n, cuts) in cuts.iter().enumerate().skip(1) {
+        let l = (n as Lit) << 1;
+        if !g.is_and(n) {
+            let c = inverter(l, &arrival, &flow);
+            arrival[l as usize + 1] = c.arrival;
+            flow[l as usize + 1] = c.flow;
+            best[l as usize + 1] = Some(c);
+            continue;
+        }
+        let mut phases: [Option<Choice>; 2] = [None, None];
+        for cut in cuts {
+            if cut.leaves == [n] {
+                continue;
+            }
+            let size = cut.leaves.len();
+            for mask in 0..1_usize << size {
+                let truth = (0..size)
+                    .filter(|j| (mask >> j) & 1 == 1)
+                    .fold(cut.truth, flip);
+                for (p, phase) in phases.iter_mut().enumerate() {
+                    let target = if p == 1 { !truth } else { truth };
+                    let Some(candidates) = lib.matches.get(&(size, target)) else {
+                        continue;
+                    };
+                    for (gate, perm) in candidates {
+                        let gate_ = &lib.gates[*gate];
+                        let inputs: Vec<Lit> = perm
+                            .iter()
+                            .map(|j| ((cut.leaves[*j] as Lit) << 1) | ((mask >> j) & 1) as Lit)
+                            .collect();
+                        let c = Choice {
+                            gate: *gate,
+                            arrival: inputs
+                                .iter()
+                                .zip(&gate_.delays)
+                                .map(|(i, d)| arrival[*i as usize] + d)
+                                .fold(0.0, f64::max),
+                            flow: gate_.area
+                                + inputs
+                                    .iter()
+                                    .map(|i| flow[*i as usize] / refs[node(*i)].max(1) as f64)
+                                    .sum::<f64>(),
+                            inputs,
+                        };
+                        if c.better(phase) {
+                            *phase = Some(c);
+                        }
+                    }
+                }
+            }
+        }
+        // a phase can also be the inverse of the other
+        let [pos, neg] = phases;
+        let set = |c: &Choice, i: usize, arrival: &mut [f64], flow: &mut [f64]| {
+            arrival[i] = c.arrival;
+            flow[i] = c.flow;
+        };
+        let (pos, neg) = match (pos, neg) {
+            (Some(pos), Some(neg)) => (pos, neg),
+            (Some(pos), None) => {
+                set(&pos, l as usize, &mut arrival, &mut flow);
+                let neg = inverter(l, &arrival, &flow);
+                (pos, neg)
+            }
+            (None, Some(neg)) => {
+                set(&neg, l as usize + 1, &mut arrival, &mut flow);
+                let pos = inverter(l + 1, &arrival, &flow);
+                (pos, neg)
+            }
+            (None, None) => panic!("library cannot map node {}", n),
+        };
+        set(&pos, l as usize, &mut arrival, &mut flow);
+        set(&neg, l as usize + 1, &mut arrival, &mut flow);
+        best[l as usize] = Some(pos);
+        best[l as usize + 1] = Some(neg);
+    }
+
+    // cover from the outputs, fanins first
+    let mut cells = vec![];
+    let mut seen = HashSet::new();
+    let mut stack: Vec<(Lit, bool)> = g.outputs.iter().rev().map(|o| (*o, false)).collect();
+    while let Some((l, done)) = stack.pop() {
+        if done {
+            let c = best[l as usize].as_ref().unwrap();
+            cells.push(Cell {
+                gate: c.gate,
+                inputs: c.inputs.clone(),
+                output: l,
+            });
+        } else if seen.insert(l) {
+            if let Some(c) = &best[l as usize] {
+                stack.push((l, true));
+                stack.extend(c.inputs.iter().rev().map(|i| (*i, false)));
+            }
+        }
+    }
+    Netlist {
+        inputs: g.inputs,
+        area: cells.iter().map(|c| lib.gates[c.gate].area).sum(),
+        delay: g
+            .outputs
+            .iter()
+            .map(|o| arrival[*o as usize])
+            .fold(0.0, f64::max),
+        cells,
+        outputs: g.outputs.clone(),
+    }
+}
+
+impl Netlist {
+    fn net(&self, l: Lit) -> String {
+        let n = node(l);
+        if n == 0 {
+            format!("c{}", l)
+        } else if n <= self.inputs && !inverted(l) {
+            format!("i{}", n - 1)
+        } else if inverted(l) {
+            format!("n{}_n", n)
+        } else {
+            format!("n{}", n)
+        }
+    }
+
+    /// Mapped BLIF, every cell as a `.gate` line
+    pub fn blif(&self, lib: &Library) -> String {
+        let mut out = String::new();
+        writeln!(out, ".model rbc").unwrap();
+        write!(out, ".inputs").unwrap();
+        for i in 0..self.inputs {
+            write!(out, " i{}", i).unwrap();
+        }
+        write!(out, "\n.outputs").unwrap();
+        for i in 0..self.outputs.len() {
+            write!(out, " o{}", i).unwrap();
+        }
+        writeln!(out).unwrap();
+        for c in &self.cells {
+            let gate = &lib.gates[c.gate];
+            write!(out, ".gate {}", gate.name).unwrap();
+            for (p, i) in gate.pins.iter().zip(&c.inputs) {
+                write!(out, " {}={}", p, self.net(*i)).unwrap();
+            }
+            writeln!(out, " {}={}", gate.output, self.net(c.output)).unwrap();
+        }
+        for (i, o) in self.outputs.iter().enumerate() {
+            match *o {
+                opt::FALSE => writeln!(out, ".names o{}", i).unwrap(),
+                opt::TRUE => writeln!(out, ".names o{}\n1", i).unwrap(),
+                _ => writeln!(out, ".names {} o{}\n1 1", self.net(*o), i).unwrap(),
+            }
+        }
+        writeln!(out, ".end").unwrap();
+        out
+    }
+
+    /// Evaluate the netlist, for checking it against the design
+    pub fn eval(&self, lib: &Library, input: &[bool]) -> Vec<bool> {
+        let mut values: HashMap<Lit, bool> =
+            HashMap::from([(opt::FALSE, false), (opt::TRUE, true)]);
+        for (i, v) in input.iter().enumerate() {
+            values.insert(((i + 1) as Lit) << 1, *v);
+        }
+        for c in &self.cells {
+            let index = c
+                .inputs
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (j, i)| acc | ((values[i] as usize) << j));
+            values.insert(c.output, (lib.gates[c.gate].truth >> index) & 1 == 1);
+        }
+        self.outputs.iter().map(|o| values[o]).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{map, Library, GENLIB};
+    use crate::opt::Graph;
+
+    #[test]
+    fn parse() {
+        let lib = Library::parse(GENLIB.as_bytes()).unwrap();
+        let aoi = lib.gates.iter().find(|g| g.name == "AOI21").unwrap();
+        assert_eq!(aoi.pins, ["a", "b", "c"]);
+        assert_eq!(aoi.delays, [1.6; 3]);
+        let lib = Library::parse(
+            b"# inverter\nGATE inv 1 Y=a';\nPIN a INV 1 999 2 0 3 0\nGATE nd 2 Y=!(a&b);",
+        )
+        .unwrap();
+        assert_eq!(lib.gates[0].delays, [3.0]);
+        assert!(matches!(
+            Library::parse(b"GATE inv 1 Y=!a;"),
+            Err(super::Error::Incomplete)
+        ));
+        assert!(matches!(
+            Library::parse(b"GATE inv 1 Y=!a"),
+            Err(super::Error::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn mapped() {
+        let lib = Library::parse(GENLIB.as_bytes()).unwrap();
+        for (file, cells) in [
+            (&include_bytes!("../verilog/adder.aag")[..], None),
+            (&include_bytes!("../verilog/popcount.aag")[..], None),
+            (b"aag 2 2 0 1 0\n2\n4\n5\n", Some(1)),
+        ] {
+            let (inputs, outputs) = crate::aag::parse(file).unwrap();
+            let g = Graph::from_aig(inputs, &outputs);
+            let netlist = map(&g, &lib);
+            if let Some(cells) = cells {
+                assert_eq!(netlist.cells.len(), cells);
+            }
+            assert!(netlist.area > 0.0);
+            for term in 0..1 << inputs {
+                let input: Vec<_> = (0..inputs).map(|i| (term >> i) & 1 == 1).collect();
+                let expected: Vec<_> = outputs.iter().map(|o| o.eval(&input)).collect();
+                assert_eq!(netlist.eval(&lib, &input), expected);
+            }
+            let blif = netlist.blif(&lib);
+            assert!(blif.starts_with(".model rbc\n"));
+        }
+    }
+}
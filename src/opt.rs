@@ -0,0 +1,604 @@
+use crate::aig::{topological, And, Const, Inv, Sym, AIG};
+use std::{collections::HashMap, rc::Rc, sync::OnceLock};
+
+/// Literal of a `Graph` node, `2 * node + inverted` as in AIGER, so 0 is
+/// false and 1 is true
+pub type Lit = u32;
+
+pub const FALSE: Lit = 0;
+pub const TRUE: Lit = 1;
+
+pub fn node(lit: Lit) -> usize {
+    (lit >> 1) as usize
+}
+
+pub fn inverted(lit: Lit) -> bool {
+    (lit & 1) == 1
+}
+
+/// Structurally hashed AIG with indexed nodes
+///
+/// Node 0 is the constant, nodes `1..=inputs` are the inputs and AND nodes
+/// follow in topological order.
+#[derive(Debug, Clone)]
+pub struct Graph {
+    pub inputs: usize,
+    fanins: Vec<(Lit, Lit)>,
+    strash: HashMap<(Lit, Lit), Lit>,
+    pub outputs: Vec<Lit>,
+}
+
+impl Graph {
+    pub fn new(inputs: usize) -> Self {
+        Self {
+            inputs,
+            fanins: vec![(FALSE, FALSE); inputs + 1],
+            strash: HashMap::new(),
+            outputs: vec![],
+        }
+    }
+
+    /// literal of input `i`
+    pub fn input(&self, i: usize) -> Lit {
+        assert!(i < self.inputs);
+        ((i + 1) as Lit) << 1
+    }
+
+    /// number of nodes, including the constant and the inputs
+    pub fn len(&self) -> usize {
+        self.fanins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fanins.len() == 1
+    }
+
+    pub fn is_and(&self, n: usize) -> bool {
+        n > self.inputs
+    }
+
+    pub fn fanins(&self, n: usize) -> (Lit, Lit) {
+        assert!(self.is_and(n));
+        self.fanins[n]
+    }
+
+    /// existing literal equal to `a & b`, without adding nodes
+    pub fn lookup(&self, a: Lit, b: Lit) -> Option<Lit> {
+        let (a, b) = (a.min(b), a.max(b));
+        if a == FALSE || a == b ^ 1 {
+            Some(FALSE)
+        } else if a == TRUE || a == b {
+            Some(b)
+        } else {
+            self.strash.get(&(a, b)).copied()
+        }
+    }
+
+    pub fn and(&mut self, a: Lit, b: Lit) -> Lit {
+        if let Some(l) = self.lookup(a, b) {
+            return l;
+        }
+        let (a, b) = (a.min(b), a.max(b));
+        let l = (self.fanins.len() as Lit) << 1;
+        self.fanins.push((a, b));
+        self.strash.insert((a, b), l);
+        l
+    }
+
+    pub fn from_aig(inputs: usize, outputs: &[Box<AIG>]) -> Self {
+        let mut g = Graph::new(inputs);
+        let mut lits: HashMap<*const AIG, Lit> = HashMap::new();
+        let lit = |lits: &HashMap<*const AIG, Lit>, aig: &AIG| lits[&(aig as *const AIG)];
+        for aig in topological(outputs.iter().map(|o| &**o)) {
+            let l = match aig {
+                AIG::A(And(a, b)) => {
+                    let (a, b) = (lit(&lits, a), lit(&lits, b));
+                    g.and(a, b)
+                }
+                AIG::I(Inv(r)) => lit(&lits, r) ^ 1,
+                AIG::G(Sym(i)) => g.input(*i),
+                AIG::C(Const(v)) => *v as Lit,
+            };
+            lits.insert(aig as *const AIG, l);
+        }
+        g.outputs = outputs.iter().map(|o| lit(&lits, o)).collect();
+        g
+    }
+
+    pub fn to_aig(&self) -> Vec<Box<AIG>> {
+        let mut nodes: Vec<Rc<AIG>> = Vec::with_capacity(self.len());
+        nodes.push(Rc::new(AIG::C(Const(false))));
+        for i in 0..self.inputs {
+            nodes.push(Rc::new(AIG::G(Sym(i))));
+        }
+        let lit = |nodes: &[Rc<AIG>], l: Lit| {
+            let n = nodes[node(l)].clone();
+            if inverted(l) {
+                AIG::inv(n)
+            } else {
+                n
+            }
+        };
+        for n in self.inputs + 1..self.len() {
+            let (a, b) = self.fanins[n];
+            let and = AIG::and(lit(&nodes, a), lit(&nodes, b));
+            nodes.push(and);
+        }
+        self.outputs
+            .iter()
+            .map(|o| Box::new(Rc::unwrap_or_clone(lit(&nodes, *o))))
+            .collect()
+    }
+
+    /// number of references to every node from AND nodes and outputs
+    /// reachable from the outputs
+    pub fn refs(&self) -> Vec<usize> {
+        let mut refs = vec![0; self.len()];
+        for o in &self.outputs {
+            refs[node(*o)] += 1;
+        }
+        for n in (self.inputs + 1..self.len()).rev() {
+            if refs[n] > 0 {
+                let (a, b) = self.fanins[n];
+                refs[node(a)] += 1;
+                refs[node(b)] += 1;
+            }
+        }
+        refs
+    }
+
+    /// number of AND nodes reachable from the outputs
+    pub fn ands(&self) -> usize {
+        let refs = self.refs();
+        (self.inputs + 1..self.len())
+            .filter(|n| refs[*n] > 0)
+            .count()
+    }
+
+    /// logic level of every node
+    pub fn levels(&self) -> Vec<usize> {
+        let mut levels = vec![0; self.len()];
+        for n in self.inputs + 1..self.len() {
+            let (a, b) = self.fanins[n];
+            levels[n] = std::cmp::max(levels[node(a)], levels[node(b)]) + 1;
+        }
+        levels
+    }
+
+    pub fn depth(&self) -> usize {
+        let levels = self.levels();
+        self.outputs
+            .iter()
+            .map(|o| levels[node(*o)])
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// copy of the graph without the nodes unreachable from the outputs
+    pub fn cleanup(&self) -> Graph {
+        let refs = self.refs();
+        let mut g = Graph::new(self.inputs);
+        let mut map: Vec<Lit> = (0..=self.inputs as Lit).map(|n| n << 1).collect();
+        map.resize(self.len(), FALSE);
+        let lit = |map: &[Lit], l: Lit| map[node(l)] ^ (l & 1);
+        for n in self.inputs + 1..self.len() {
+            if refs[n] > 0 {
+                let (a, b) = self.fanins[n];
+                map[n] = g.and(lit(&map, a), lit(&map, b));
+            }
+        }
+        g.outputs = self.outputs.iter().map(|o| lit(&map, *o)).collect();
+        g
+    }
+}
+
+/// Truth table of input `i` of a cut with up to 6 inputs
+pub const VARS: [u64; 6] = [
+    0xAAAA_AAAA_AAAA_AAAA,
+    0xCCCC_CCCC_CCCC_CCCC,
+    0xF0F0_F0F0_F0F0_F0F0,
+    0xFF00_FF00_FF00_FF00,
+    0xFFFF_0000_FFFF_0000,
+    0xFFFF_FFFF_0000_0000,
+];
+
+/// Cut of a node, `truth` is the function of the node over `leaves`, leaf
+/// `i` being variable `i`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Cut {
+    pub leaves: Vec<usize>,
+    pub truth: u64,
+}
+
+impl Cut {
+    pub fn trivial(n: usize) -> Self {
+        Self {
+            leaves: vec![n],
+            truth: VARS[0],
+        }
+    }
+
+    /// truth table over `leaves`, a superset of the leaves of the cut
+    fn expand(&self, leaves: &[usize]) -> u64 {
+        let pos: Vec<usize> = self
+            .leaves
+            .iter()
+            .map(|l| leaves.iter().position(|x| x == l).unwrap())
+            .collect();
+        let mut truth = 0;
+        for m in 0..64 {
+            let index = pos
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (j, p)| acc | (((m >> p) & 1) << j));
+            truth |= ((self.truth >> index) & 1) << m;
+        }
+        truth
+    }
+
+    pub fn dominates(&self, other: &Self) -> bool {
+        self.leaves.iter().all(|l| other.leaves.contains(l))
+    }
+
+    /// Cut of the AND of literals `a` and `b` from their cuts `ca` and `cb`,
+    /// if it has at most `k` leaves
+    pub fn merge(ca: &Cut, a: Lit, cb: &Cut, b: Lit, k: usize) -> Option<Cut> {
+        let mut leaves = ca.leaves.clone();
+        leaves.extend(&cb.leaves);
+        leaves.sort();
+        leaves.dedup();
+        if leaves.len() > k {
+            return None;
+        }
+        let ta = ca.expand(&leaves) ^ if inverted(a) { !0 } else { 0 };
+        let tb = cb.expand(&leaves) ^ if inverted(b) { !0 } else { 0 };
+        Some(Cut {
+            leaves,
+            truth: ta & tb,
+        })
+    }
+}
+
+/// All `k`-feasible cuts of every node, at most `limit` per node besides
+/// the trivial one, smaller cuts first
+pub fn cuts(graph: &Graph, k: usize, limit: usize) -> Vec<Vec<Cut>> {
+    assert!(k <= 6);
+    let mut cuts: Vec<Vec<Cut>> = Vec::with_capacity(graph.len());
+    cuts.push(vec![Cut {
+        leaves: vec![],
+        truth: 0,
+    }]);
+    for n in 1..=graph.inputs {
+        cuts.push(vec![Cut::trivial(n)]);
+    }
+    for n in graph.inputs + 1..graph.len() {
+        let (a, b) = graph.fanins(n);
+        let mut result: Vec<Cut> = vec![];
+        for ca in &cuts[node(a)] {
+            for cb in &cuts[node(b)] {
+                let Some(cut) = Cut::merge(ca, a, cb, b, k) else {
+                    continue;
+                };
+                if result.iter().any(|c| c.dominates(&cut)) {
+                    continue;
+                }
+                result.retain(|c| !cut.dominates(c));
+                result.push(cut);
+            }
+        }
+        result.sort_by_key(|c| c.leaves.len());
+        result.truncate(limit);
+        result.push(Cut::trivial(n));
+        cuts.push(result);
+    }
+    cuts
+}
+
+/// How to build a 4-input function from smaller ones
+#[derive(Debug, Copy, Clone)]
+enum Recipe {
+    Unknown,
+    /// constant or variable
+    Leaf,
+    Not(u16),
+    And(u16, u16),
+}
+
+/// Largest number of AND nodes of a library structure
+const LIBRARY_COST: u8 = 6;
+
+/// Minimum AND count structures of 4-input functions, as trees with free
+/// inverters, computed once by enumerating functions in order of cost
+///
+/// Trees of at most `LIBRARY_COST` ANDs only reach 17,222 of the 65,536
+/// functions, and structures sharing nodes inside the cut are not
+/// enumerated, so this is not an optimal DAG for every NPN class. Cuts of
+/// the other functions have no structure and are never rewritten.
+struct Library {
+    cost: Vec<u8>,
+    recipe: Vec<Recipe>,
+}
+
+fn library() -> &'static Library {
+    static LIBRARY: OnceLock<Library> = OnceLock::new();
+    LIBRARY.get_or_init(|| {
+        let mut cost = vec![u8::MAX; 1 << 16];
+        let mut recipe = vec![Recipe::Unknown; 1 << 16];
+        let mut levels: Vec<Vec<u16>> = vec![vec![]];
+        for t in [0x0000, 0xFFFF]
+            .into_iter()
+            .chain(VARS[..4].iter().flat_map(|v| [*v as u16, !*v as u16]))
+        {
+            cost[t as usize] = 0;
+            recipe[t as usize] = Recipe::Leaf;
+            levels[0].push(t);
+        }
+        for c in 1..=LIBRARY_COST {
+            let mut level = vec![];
+            for i in 0..c as usize {
+                let j = c as usize - 1 - i;
+                if i > j {
+                    break;
+                }
+                for (x, f) in levels[i].iter().enumerate() {
+                    let start = if i == j { x } else { 0 };
+                    for g in &levels[j][start..] {
+                        let h = f & g;
+                        if cost[h as usize] == u8::MAX {
+                            cost[h as usize] = c;
+                            recipe[h as usize] = Recipe::And(*f, *g);
+                            cost[!h as usize] = c;
+                            recipe[!h as usize] = Recipe::Not(h);
+                            level.push(h);
+                            level.push(!h);
+                        }
+                    }
+                }
+            }
+            levels.push(level);
+        }
+        Library { cost, recipe }
+    })
+}
+
+impl Library {
+    fn leaf(t: u16, leaves: &[Lit]) -> Lit {
+        match t {
+            0x0000 => FALSE,
+            0xFFFF => TRUE,
+            _ => {
+                let i = VARS[..4]
+                    .iter()
+                    .position(|v| *v as u16 == t || !*v as u16 == t)
+                    .unwrap();
+                leaves[i] ^ (VARS[i] as u16 != t) as Lit
+            }
+        }
+    }
+
+    /// literal of `t` over `leaves` if it exists in `g`, otherwise the
+    /// number of AND nodes to add is added to `count`
+    fn dry(&self, g: &Graph, t: u16, leaves: &[Lit], count: &mut usize) -> Option<Lit> {
+        match self.recipe[t as usize] {
+            Recipe::Unknown => unreachable!(),
+            Recipe::Leaf => Some(Self::leaf(t, leaves)),
+            Recipe::Not(h) => self.dry(g, h, leaves, count).map(|l| l ^ 1),
+            Recipe::And(a, b) => {
+                let a = self.dry(g, a, leaves, count);
+                let b = self.dry(g, b, leaves, count);
+                match (a, b) {
+                    (Some(a), Some(b)) if g.lookup(a, b).is_some() => g.lookup(a, b),
+                    _ => {
+                        *count += 1;
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    fn build(&self, g: &mut Graph, t: u16, leaves: &[Lit]) -> Lit {
+        match self.recipe[t as usize] {
+            Recipe::Unknown => unreachable!(),
+            Recipe::Leaf => Self::leaf(t, leaves),
+            Recipe::Not(h) => self.build(g, h, leaves) ^ 1,
+            Recipe::And(a, b) => {
+                let a = self.build(g, a, leaves);
+                let b = self.build(g, b, leaves);
+                g.and(a, b)
+            }
+        }
+    }
+}
+
+/// Number of nodes only used by `n` down to `leaves`, dereferencing them
+/// in `refs` when `deref` is set and referencing them back otherwise
+fn mffc(g: &Graph, refs: &mut [usize], n: usize, leaves: &[usize], deref: bool) -> usize {
+    let mut count = 0;
+    let mut stack = vec![n];
+    while let Some(n) = stack.pop() {
+        count += 1;
+        let (a, b) = g.fanins(n);
+        for child in [node(a), node(b)] {
+            let inner = g.is_and(child) && !leaves.contains(&child);
+            if deref {
+                refs[child] -= 1;
+                if inner && refs[child] == 0 {
+                    stack.push(child);
+                }
+            } else {
+                if inner && refs[child] == 0 {
+                    stack.push(child);
+                }
+                refs[child] += 1;
+            }
+        }
+    }
+    count
+}
+
+/// One pass of DAG-aware rewriting: every AND node is rebuilt from the
+/// library structure of one of its 4-input cuts when that saves more nodes
+/// of its maximum fanout free cone than the structure adds, and keeps its
+/// fanins when no cut has a structure in the library
+fn rewrite_once(g: &Graph) -> Graph {
+    let lib = library();
+    let cuts = cuts(g, 4, 8);
+    let mut refs = g.refs();
+    let mut h = Graph::new(g.inputs);
+    let mut map: Vec<Lit> = (0..=g.inputs as Lit).map(|n| n << 1).collect();
+    map.resize(g.len(), FALSE);
+    let lit = |map: &[Lit], l: Lit| map[node(l)] ^ (l & 1);
+
+    for n in g.inputs + 1..g.len() {
+        if refs[n] == 0 {
+            continue;
+        }
+        let mut best: Option<(usize, u16, Vec<Lit>)> = None;
+        for cut in &cuts[n] {
+            if cut.leaves.len() < 2 || cut.leaves == [n] {
+                continue;
+            }
+            let t = cut.truth as u16;
+            if lib.cost[t as usize] == u8::MAX {
+                continue;
+            }
+            let saved = mffc(g, &mut refs, n, &cut.leaves, true);
+            mffc(g, &mut refs, n, &cut.leaves, false);
+            let leaves: Vec<Lit> = cut.leaves.iter().map(|l| map[*l]).collect();
+            let mut added = 0;
+            lib.dry(&h, t, &leaves, &mut added);
+            if added < saved
+                && best
+                    .as_ref()
+                    .is_none_or(|(gain, _, _)| saved - added > *gain)
+            {
+                best = Some((saved - added, t, leaves));
+            }
+        }
+        map[n] = match best {
+            Some((_, t, leaves)) => lib.build(&mut h, t, &leaves),
+            None => {
+                let (a, b) = g.fanins(n);
+                h.and(lit(&map, a), lit(&map, b))
+            }
+        };
+    }
+    h.outputs = g.outputs.iter().map(|o| lit(&map, *o)).collect();
+    h.cleanup()
+}
+
+/// DAG-aware rewriting of 4-input cuts, repeated while the number of AND
+/// nodes drops
+pub fn rewrite(g: &Graph) -> Graph {
+    let mut best = g.cleanup();
+    loop {
+        let next = rewrite_once(&best);
+        if next.ands() >= best.ands() {
+            return best;
+        }
+        best = next;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{cuts, library, rewrite, Graph, VARS};
+    use crate::aig::AIG;
+
+    #[test]
+    fn graph() {
+        let mut g = Graph::new(2);
+        let (a, b) = (g.input(0), g.input(1));
+        let x = g.and(a, b);
+        assert_eq!(g.and(b, a), x);
+        assert_eq!(g.and(a, a ^ 1), 0);
+        assert_eq!(g.and(a, 1), a);
+        g.outputs = vec![x ^ 1];
+        assert_eq!(g.ands(), 1);
+        let aig = g.to_aig();
+        assert_eq!(aig[0].to_string(), "!(i0 & i1)");
+        let h = Graph::from_aig(2, &aig);
+        assert_eq!(h.ands(), 1);
+        assert_eq!(h.outputs, g.outputs);
+    }
+
+    #[test]
+    fn cut() {
+        // (a & b) & !(b & c)
+        let mut g = Graph::new(3);
+        let (a, b, c) = (g.input(0), g.input(1), g.input(2));
+        let ab = g.and(a, b);
+        let bc = g.and(b, c);
+        let f = g.and(ab, bc ^ 1);
+        let cuts = cuts(&g, 4, 8);
+        let top = &cuts[super::node(f)];
+        let full = top.iter().find(|c| c.leaves == [1, 2, 3]).unwrap();
+        assert_eq!(full.truth, VARS[0] & VARS[1] & !(VARS[1] & VARS[2]));
+        assert!(top
+            .iter()
+            .any(|c| c.leaves == [super::node(ab), super::node(bc)]));
+    }
+
+    #[test]
+    fn lib() {
+        let lib = library();
+        let (a, b) = (VARS[0] as u16, VARS[1] as u16);
+        assert_eq!(lib.cost[(a & b) as usize], 1);
+        assert_eq!(lib.cost[(a | b) as usize], 1);
+        assert_eq!(lib.cost[(a ^ b) as usize], 3);
+        // the functions of tree structures of up to 6 ANDs
+        let covered = lib.cost.iter().filter(|c| **c != u8::MAX).count();
+        assert_eq!(covered, 17_222);
+        let xor4 = VARS[..4].iter().fold(0, |acc, v| acc ^ *v as u16);
+        assert_eq!(lib.cost[xor4 as usize], u8::MAX);
+    }
+
+    #[test]
+    fn rewritten() {
+        // a & b & c & d written redundantly
+        let v: Vec<Box<AIG>> = (0..4).map(|i| i.into()).collect();
+        let f = ((v[0].clone() & v[1].clone()) & (v[1].clone() & v[2].clone()))
+            & ((v[2].clone() & v[3].clone()) & (v[0].clone() & v[3].clone()));
+        let g = Graph::from_aig(4, std::slice::from_ref(&f));
+        assert_eq!(g.ands(), 7);
+        let h = rewrite(&g);
+        assert_eq!(h.ands(), 3);
+        let r = h.to_aig();
+        for term in 0..16 {
+            let input: Vec<_> = (0..4).map(|i| (term >> i) & 1 == 1).collect();
+            assert_eq!(f.eval(&input), r[0].eval(&input));
+        }
+    }
+
+    #[test]
+    fn redundant() {
+        // (a & b) | (a & c) is a & (b | c), and a & (a & b) is a & b
+        let v: Vec<Box<AIG>> = (0..3).map(|i| i.into()).collect();
+        let f = (v[0].clone() & v[1].clone()) | (v[0].clone() & v[2].clone());
+        let g = v[0].clone() & (v[0].clone() & v[1].clone());
+        let outputs = vec![f, g];
+        let before = Graph::from_aig(3, &outputs);
+        assert_eq!(before.ands(), 4);
+        let after = rewrite(&before);
+        // a & b, b | c and a & (b | c)
+        assert_eq!((after.ands(), after.depth()), (3, 2));
+        let mut m = crate::bdd::Manager::new(3);
+        for (a, b) in outputs.iter().zip(after.to_aig()) {
+            assert_eq!(m.from_aig(a), m.from_aig(&b));
+        }
+    }
+
+    #[test]
+    fn equivalent() {
+        let (inputs, outputs) =
+            crate::pla::parse(include_bytes!("../verilog/popcount.pla")).unwrap();
+        let g = Graph::from_aig(inputs, &outputs);
+        let h = rewrite(&g);
+        assert!(h.ands() < g.ands());
+        let mut m = crate::bdd::Manager::new(inputs);
+        for (a, b) in outputs.iter().zip(h.to_aig()) {
+            assert_eq!(m.from_aig(a), m.from_aig(&b));
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::aig;
+use nom::{
+    branch::alt,
+    bytes::complete::{is_a, is_not, tag},
+    character::complete::{char, not_line_ending, space0, space1, u64},
+    combinator::{all_consuming, map},
+    multi::many0,
+    sequence::{preceded, separated_pair, terminated},
+    Finish, IResult,
+};
+use std::fmt::Display;
+
+pub type Outputs = Vec<Box<aig::AIG>>;
+
+enum Line<'a> {
+    Inputs(u64),
+    Outputs(u64),
+    /// input names of `.ilb`
+    Names(Vec<&'a [u8]>),
+    /// any other keyword, output names, number of cubes, type and end
+    Directive,
+    Cube(&'a [u8], &'a [u8]),
+}
+
+fn line(input: &[u8]) -> IResult<&[u8], Line<'_>> {
+    all_consuming(terminated(
+        alt((
+            map(
+                preceded(tag(b".ilb"), many0(preceded(space1, is_not(" \t")))),
+                Line::Names,
+            ),
+            map(preceded(tag(b".i"), preceded(space1, u64)), Line::Inputs),
+            map(preceded(tag(b".o"), preceded(space1, u64)), Line::Outputs),
+            map(preceded(char('.'), not_line_ending), |_| Line::Directive),
+            map(
+                separated_pair(is_a("01-"), space1, is_a("01-~")),
+                |(i, o)| Line::Cube(i, o),
+            ),
+        )),
+        space0,
+    ))(input)
+}
+
+#[derive(Debug)]
+pub enum Error<'a> {
+    /// malformed PLA text
+    Syntax(nom::error::Error<&'a [u8]>),
+    /// 1-based line of a cube that does not match `.i` or `.o`
+    Width(usize),
+}
+
+impl<'a> From<nom::error::Error<&'a [u8]>> for Error<'a> {
+    fn from(value: nom::error::Error<&'a [u8]>) -> Self {
+        Self::Syntax(value)
+    }
+}
+
+impl Display for Error<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Syntax(e) => write!(
+                f,
+                "syntax error: {:?} at {:?}",
+                e.code,
+                String::from_utf8_lossy(e.input)
+            ),
+            Error::Width(line) => write!(f, "cube on line {} has the wrong width", line),
+        }
+    }
+}
+
+/// Parse a PLA in the `fd` type, every output is the OR of the cubes with a
+/// `1` in its column
+pub fn parse(input: &[u8]) -> Result<(usize, Outputs), Error<'_>> {
+    let mut inputs = None;
+    let mut outputs = None;
+    let mut cubes = vec![];
+
+    for (n, text) in input.split(|c| *c == b'\n').enumerate() {
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+        if text.iter().all(u8::is_ascii_whitespace) || text.starts_with(b"#") {
+            continue;
+        }
+        match line(text).finish()?.1 {
+            Line::Inputs(i) => inputs = Some(i as usize),
+            Line::Outputs(o) => outputs = Some(o as usize),
+            Line::Directive | Line::Names(_) => (),
+            Line::Cube(i, o) => {
+                if *inputs.get_or_insert(i.len()) != i.len()
+                    || *outputs.get_or_insert(o.len()) != o.len()
+                {
+                    return Err(Error::Width(n + 1));
+                }
+                cubes.push((i, o));
+            }
+        }
+    }
+
+    let terms: Vec<Box<aig::AIG>> = cubes
+        .iter()
+        .map(|(i, _)| {
+            i.iter()
+                .enumerate()
+                .fold(true.into(), |acc: Box<aig::AIG>, (v, c)| match c {
+                    b'1' => acc & v.into(),
+                    b'0' => acc & !Box::<aig::AIG>::from(v),
+                    _ => acc,
+                })
+        })
+        .collect();
+
+    let outputs = (0..outputs.unwrap_or(0))
+        .map(|j| {
+            cubes
+                .iter()
+                .zip(&terms)
+                .filter(|((_, o), _)| o[j] == b'1')
+                .fold(false.into(), |acc: Box<aig::AIG>, (_, term)| {
+                    acc | term.clone()
+                })
+        })
+        .collect();
+
+    Ok((inputs.unwrap_or(0), outputs))
+}
+
+/// Input names given by `.ilb`, as `(input, name)`
+pub fn names(input: &[u8]) -> Vec<(usize, String)> {
+    input
+        .split(|c| *c == b'\n')
+        .map(|text| text.strip_suffix(b"\r").unwrap_or(text))
+        .find_map(|text| match line(text).finish() {
+            Ok((_, Line::Names(names))) => Some(names),
+            _ => None,
+        })
+        .unwrap_or_default()
+        .into_iter()
+        .map(|n| String::from_utf8_lossy(n).into_owned())
+        .enumerate()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn parse() {
+        let pla = b"# comment\n.i 3\n.o 2\n.ilb a b c\n.p 3\n1-0 10\n-11 11\n000 0-\n.e\n";
+        let (inputs, outputs) = super::parse(pla).unwrap();
+        assert_eq!(inputs, 3);
+        assert_eq!(outputs.len(), 2);
+        for term in 0..8 {
+            let input: Vec<_> = (0..3).map(|i| (term >> i) & 1 == 1).collect();
+            let (a, b, c) = (input[0], input[1], input[2]);
+            assert_eq!(outputs[0].eval(&input), (a && !c) || (b && c));
+            assert_eq!(outputs[1].eval(&input), b && c);
+        }
+        assert_eq!(
+            super::names(pla),
+            [
+                (0, "a".to_string()),
+                (1, "b".to_string()),
+                (2, "c".to_string())
+            ]
+        );
+        assert!(super::names(b".i 1\n.o 1\n1 1\n").is_empty());
+        assert!(matches!(
+            super::parse(b".i 3\n.o 1\n10 1\n"),
+            Err(super::Error::Width(3))
+        ));
+        assert!(matches!(
+            super::parse(b".i 3\n.o 1\n1x0 1\n"),
+            Err(super::Error::Syntax(_))
+        ));
+    }
+}
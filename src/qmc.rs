@@ -1,6 +1,6 @@
-use std::{collections::HashSet, fmt::Display, hash::Hash, ops::Sub};
+use std::{collections::HashSet, fmt::Display, hash::Hash};
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
 pub enum Tri {
     /// false
     F,
@@ -24,7 +24,19 @@ impl Display for Tri {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+/// literals of `tris` named by `names`, complemented ones with a `'`
+fn named(tris: &[Tri], names: &[String]) -> Vec<String> {
+    tris.iter()
+        .zip(names)
+        .filter_map(|(t, n)| match t {
+            Tri::T => Some(n.clone()),
+            Tri::F => Some(format!("{}'", n)),
+            Tri::X => None,
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
 pub struct Imp(pub Vec<Tri>);
 
 impl Display for Imp {
@@ -42,6 +54,23 @@ impl Display for Imp {
 }
 
 impl Imp {
+    /// product of literals like `a' b c`, input `i` being named `names[i]`
+    pub fn product(&self, names: &[String]) -> String {
+        let lits = named(&self.0, names);
+        if lits.is_empty() {
+            "1".to_string()
+        } else {
+            lits.join(" ")
+        }
+    }
+    /// index of a minterm, input 0 being the least significant bit
+    pub fn index(&self) -> Option<usize> {
+        self.0.iter().rev().try_fold(0, |acc, t| match t {
+            Tri::T => Some(acc << 1 | 1),
+            Tri::F => Some(acc << 1),
+            Tri::X => None,
+        })
+    }
     pub fn literals(&self) -> usize {
         self.0
             .iter()
@@ -85,36 +114,391 @@ impl Imp {
     }
 }
 
-fn reduce_one(minterms: &HashSet<Imp>) -> (HashSet<Imp>, HashSet<Imp>) {
-    let mut next = HashSet::<Imp>::default();
-    let mut used = HashSet::<Imp>::default();
-    for a in minterms {
-        for b in minterms {
-            if a == b {
-                continue;
+/// Implicant packed as bit pairs, bit `i` of the positive (negative) mask
+/// is set when variable `i` is `Tri::T` (`Tri::F`), `Tri::X` otherwise
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Cube {
+    vars: usize,
+    /// positive mask words followed by negative mask words
+    bits: Box<[u64]>,
+}
+
+impl Cube {
+    fn words(&self) -> usize {
+        self.bits.len() / 2
+    }
+    pub fn pos(&self) -> &[u64] {
+        &self.bits[..self.words()]
+    }
+    pub fn neg(&self) -> &[u64] {
+        &self.bits[self.words()..]
+    }
+    pub fn vars(&self) -> usize {
+        self.vars
+    }
+    /// number of `Tri::T`, used to group cubes in QMC
+    pub fn ones(&self) -> usize {
+        self.pos().iter().map(|w| w.count_ones() as usize).sum()
+    }
+    pub fn literals(&self) -> usize {
+        self.bits.iter().map(|w| w.count_ones() as usize).sum()
+    }
+    pub fn get(&self, i: usize) -> Tri {
+        let (w, b) = (i / 64, 1 << (i % 64));
+        if self.pos()[w] & b != 0 {
+            Tri::T
+        } else if self.neg()[w] & b != 0 {
+            Tri::F
+        } else {
+            Tri::X
+        }
+    }
+    pub fn contains(&self, other: &Self) -> bool {
+        assert_eq!(self.vars, other.vars);
+        // every literal of self is a literal of other
+        self.bits
+            .iter()
+            .zip(other.bits.iter())
+            .all(|(l, r)| l & !r == 0)
+    }
+    pub fn merge(&self, other: &Self) -> Option<Self> {
+        assert_eq!(self.vars, other.vars);
+        let (pos, neg) = (self.pos(), self.neg());
+        let (opos, oneg) = (other.pos(), other.neg());
+        let mut diff = 0;
+        let mut bits = self.bits.clone();
+        let words = self.words();
+        for w in 0..words {
+            if pos[w] | neg[w] != opos[w] | oneg[w] {
+                // different don't care positions
+                return None;
             }
-            if let Some(v) = a.merge(b) {
-                next.insert(v);
-                used.insert(a.clone());
-                used.insert(b.clone());
+            let d = pos[w] ^ opos[w];
+            diff += d.count_ones();
+            bits[w] &= !d;
+            bits[words + w] &= !d;
+        }
+        if diff == 1 {
+            Some(Self {
+                vars: self.vars,
+                bits,
+            })
+        } else {
+            None
+        }
+    }
+    /// `(other, merged)` for every cube of `lo` that differs from self by a
+    /// single `Tri::F` in place of a `Tri::T`, found by hashing instead of
+    /// comparing against every cube of `lo`
+    fn merges<'a>(&self, lo: &HashSet<&'a Cube>) -> Vec<(&'a Cube, Cube)> {
+        let words = self.words();
+        let mut result = vec![];
+        for w in 0..words {
+            let mut ones = self.bits[w];
+            while ones != 0 {
+                let bit = ones & ones.wrapping_neg();
+                ones &= ones - 1;
+                let mut probe = self.clone();
+                probe.bits[w] &= !bit;
+                probe.bits[words + w] |= bit;
+                if let Some(other) = lo.get(&probe) {
+                    probe.bits[words + w] &= !bit;
+                    result.push((*other, probe));
+                }
             }
         }
+        result
+    }
+}
+
+impl From<&Imp> for Cube {
+    fn from(value: &Imp) -> Self {
+        let vars = value.0.len();
+        let words = vars.div_ceil(64);
+        let mut bits = vec![0; words * 2].into_boxed_slice();
+        for (i, t) in value.0.iter().enumerate() {
+            match t {
+                Tri::T => bits[i / 64] |= 1 << (i % 64),
+                Tri::F => bits[words + i / 64] |= 1 << (i % 64),
+                Tri::X => (),
+            }
+        }
+        Self { vars, bits }
+    }
+}
+
+impl From<&Cube> for Imp {
+    fn from(value: &Cube) -> Self {
+        Self((0..value.vars).map(|i| value.get(i)).collect())
+    }
+}
+
+/// Split cubes into groups by number of ones
+fn group(cubes: &HashSet<Cube>) -> Vec<HashSet<&Cube>> {
+    let mut groups: Vec<HashSet<&Cube>> = vec![];
+    for cube in cubes {
+        let ones = cube.ones();
+        if groups.len() <= ones {
+            groups.resize(ones + 1, HashSet::default());
+        }
+        groups[ones].insert(cube);
+    }
+    groups
+}
+
+/// Merge the cubes of `hi` with the cubes of `lo`, which have one less one
+fn merge_groups<'a>(lo: &HashSet<&'a Cube>, hi: &HashSet<&'a Cube>) -> (Vec<Cube>, Vec<&'a Cube>) {
+    let mut next = vec![];
+    let mut used = vec![];
+    for b in hi {
+        for (a, v) in b.merges(lo) {
+            next.push(v);
+            used.push(a);
+            used.push(*b);
+        }
+    }
+    (next, used)
+}
+
+#[cfg(feature = "parallel")]
+fn par_merge_groups<'a>(
+    lo: &HashSet<&'a Cube>,
+    hi: &HashSet<&'a Cube>,
+) -> (Vec<Cube>, Vec<&'a Cube>) {
+    use rayon::prelude::*;
+    let merged: Vec<_> = hi
+        .par_iter()
+        .flat_map_iter(|b| b.merges(lo).into_iter().map(move |(a, v)| (a, *b, v)))
+        .collect();
+    let mut next = vec![];
+    let mut used = vec![];
+    for (a, b, v) in merged {
+        next.push(v);
+        used.push(a);
+        used.push(b);
+    }
+    (next, used)
+}
+
+/// Split the merge results of a round into the cubes that were never merged
+/// and the cubes of the next round
+fn collect_round<'a>(
+    cubes: &'a HashSet<Cube>,
+    merged: impl IntoIterator<Item = (Vec<Cube>, Vec<&'a Cube>)>,
+) -> (Vec<Cube>, HashSet<Cube>) {
+    let mut next = HashSet::<Cube>::default();
+    let mut used = HashSet::<&Cube>::default();
+    for (n, u) in merged {
+        next.extend(n);
+        used.extend(u);
     }
-    (minterms.sub(&used), next)
+    let rem = cubes
+        .iter()
+        .filter(|c| !used.contains(c))
+        .cloned()
+        .collect();
+    (rem, next)
+}
+
+fn reduce_one(cubes: &HashSet<Cube>) -> (Vec<Cube>, HashSet<Cube>) {
+    let groups = group(cubes);
+    collect_round(cubes, groups.windows(2).map(|w| merge_groups(&w[0], &w[1])))
+}
+
+#[cfg(feature = "parallel")]
+fn par_reduce_one(cubes: &HashSet<Cube>) -> (Vec<Cube>, HashSet<Cube>) {
+    use rayon::prelude::*;
+    let groups = group(cubes);
+    let merged: Vec<_> = groups
+        .par_windows(2)
+        .map(|w| par_merge_groups(&w[0], &w[1]))
+        .collect();
+    collect_round(cubes, merged)
+}
+
+fn primes_with(
+    cubes: &HashSet<Cube>,
+    round: impl Fn(&HashSet<Cube>) -> (Vec<Cube>, HashSet<Cube>),
+) -> HashSet<Cube> {
+    let mut essential = HashSet::<Cube>::default();
+    let mut curr = cubes.clone();
+    while !curr.is_empty() {
+        let (rem, next) = round(&curr);
+        essential.extend(rem);
+        curr = next;
+    }
+    essential
+}
+
+/// Prime implicants of a set of cubes
+pub fn primes(cubes: &HashSet<Cube>) -> HashSet<Cube> {
+    primes_with(cubes, reduce_one)
+}
+
+/// Prime implicants of a set of cubes, adjacent groups are merged on the rayon thread pool
+#[cfg(feature = "parallel")]
+pub fn par_primes(cubes: &HashSet<Cube>) -> HashSet<Cube> {
+    primes_with(cubes, par_reduce_one)
 }
 
 pub fn reduce(minterms: &HashSet<Imp>) -> HashSet<Imp> {
-    let mut essential = HashSet::<Imp>::default();
-    let mut curr = minterms.clone();
+    primes(&minterms.iter().map(Cube::from).collect())
+        .iter()
+        .map(Imp::from)
+        .collect()
+}
+
+#[cfg(feature = "parallel")]
+pub fn par_reduce(minterms: &HashSet<Imp>) -> HashSet<Imp> {
+    par_primes(&minterms.iter().map(Cube::from).collect())
+        .iter()
+        .map(Imp::from)
+        .collect()
+}
+
+/// Prime chosen while covering, for the minterm `minterm`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Step {
+    pub prime: Imp,
+    pub minterm: Imp,
+    /// the only prime containing `minterm`, otherwise the first one of a
+    /// minterm when no minterm has a single prime left
+    pub essential: bool,
+    /// minterms not yet covered that the prime covers
+    pub covered: Vec<Imp>,
+}
+
+/// Choose primes covering every minterm, essential ones first, falling back
+/// to the first prime of an uncovered minterm when no column has a single one
+pub fn cover(minterms: &HashSet<Imp>, primes: &HashSet<Imp>) -> HashSet<Imp> {
+    cover_steps(minterms, primes)
+        .into_iter()
+        .map(|s| s.prime)
+        .collect()
+}
+
+/// The choices made by `cover`, in order. Minterms and primes are visited
+/// in sorted order so that ties are always broken the same way
+pub fn cover_steps(minterms: &HashSet<Imp>, primes: &HashSet<Imp>) -> Vec<Step> {
+    let (minterms, primes) = (sorted(minterms), sorted(primes));
+    // containment is checked on the packed cubes, columns and rows are indices
+    let packed: Vec<Cube> = minterms.iter().map(Cube::from).collect();
+    let rows: Vec<Cube> = primes.iter().map(Cube::from).collect();
+    let mut columns: Vec<usize> = (0..minterms.len()).collect();
+    let mut chosen = vec![false; rows.len()];
+    let mut steps = vec![];
+
+    let mut fallback = false;
+
     loop {
-        let (rem, next) = reduce_one(&curr);
-        essential.extend(rem);
-        curr = next.clone();
-        if next.is_empty() {
+        let mut covered = vec![false; minterms.len()];
+        let mut progress = false;
+
+        for &col in &columns {
+            let cover: Vec<usize> = (0..rows.len())
+                .filter(|p| rows[*p].contains(&packed[col]))
+                .collect();
+            if cover.len() == 1 || fallback {
+                let essential = !fallback;
+                fallback = false;
+                let p = cover[0];
+                if chosen[p] {
+                    continue;
+                }
+                chosen[p] = true;
+                let mut step = Step {
+                    prime: primes[p].clone(),
+                    minterm: minterms[col].clone(),
+                    essential,
+                    covered: vec![],
+                };
+                for &c in &columns {
+                    if !covered[c] && rows[p].contains(&packed[c]) {
+                        covered[c] = true;
+                        progress = true;
+                        step.covered.push(minterms[c].clone());
+                    }
+                }
+                steps.push(step);
+            }
+        }
+
+        columns.retain(|c| !covered[*c]);
+
+        if columns.is_empty() {
             break;
         }
+
+        fallback = !progress;
     }
-    essential
+
+    steps
+}
+
+/// Implicants in a fixed order, for reproducible output
+pub fn sorted(imps: &HashSet<Imp>) -> Vec<Imp> {
+    let mut v: Vec<Imp> = imps.iter().cloned().collect();
+    v.sort();
+    v
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Invalid {
+    /// minterm of the function outside of the cover
+    Uncovered(Imp),
+    /// cube of the cover that contains a maxterm of the function
+    Overcovered(Imp),
+    /// cube of the cover that stays inside the function without one of its literals
+    NotPrime(Imp),
+}
+
+impl Display for Invalid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Invalid::Uncovered(m) => write!(f, "minterm {} is not covered", m),
+            Invalid::Overcovered(c) => write!(f, "cube {} covers a maxterm", c),
+            Invalid::NotPrime(c) => write!(f, "cube {} is not prime", c),
+        }
+    }
+}
+
+/// Check that `cover` is exactly the ON-Set of `f` over `vars` inputs and
+/// that all of its cubes are prime
+pub fn verify(
+    cover: &HashSet<Imp>,
+    vars: usize,
+    f: impl Fn(&[bool]) -> bool,
+) -> Result<(), Invalid> {
+    // cubes are checked in sorted order so that the same one is reported
+    let cover = sorted(cover);
+    let cubes: Vec<Cube> = cover.iter().map(Cube::from).collect();
+    let mut maxterms = vec![];
+    for term in 0..1_usize << vars {
+        let input: Vec<bool> = (0..vars).map(|i| ((term >> i) & 1) == 1).collect();
+        let minterm = Imp(input
+            .iter()
+            .map(|v| if *v { Tri::T } else { Tri::F })
+            .collect());
+        let packed = Cube::from(&minterm);
+        let covered = cubes.iter().position(|c| c.contains(&packed));
+        match (f(&input), covered) {
+            (true, None) => return Err(Invalid::Uncovered(minterm)),
+            (false, Some(c)) => return Err(Invalid::Overcovered(Imp::from(&cubes[c]))),
+            (false, None) => maxterms.push(packed),
+            (true, Some(_)) => (),
+        }
+    }
+    for imp in &cover {
+        for i in (0..vars).filter(|i| imp.0[*i] != Tri::X) {
+            let mut wider = imp.clone();
+            wider.0[i] = Tri::X;
+            let wider = Cube::from(&wider);
+            if !maxterms.iter().any(|m| wider.contains(m)) {
+                return Err(Invalid::NotPrime(imp.clone()));
+            }
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
@@ -136,6 +520,33 @@ impl From<Imp> for ImpMax {
     }
 }
 
+impl ImpMax {
+    /// sum of literals like `(a + b' + c)`, input `i` being named `names[i]`
+    pub fn sum(&self, names: &[String]) -> String {
+        let lits = named(&self.0, names);
+        if lits.is_empty() {
+            "(0)".to_string()
+        } else {
+            format!("({})", lits.join(" + "))
+        }
+    }
+}
+
+/// Terms in index notation like `Σm(1,3,5)`, with `ΠM` for maxterms
+pub fn indices(terms: &HashSet<Imp>, maxterms: bool) -> String {
+    let mut indices: Vec<usize> = terms.iter().filter_map(Imp::index).collect();
+    indices.sort();
+    format!(
+        "{}({})",
+        if maxterms { "ΠM" } else { "Σm" },
+        indices
+            .iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
+
 impl Display for ImpMax {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -151,10 +562,33 @@ impl Display for ImpMax {
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use std::collections::HashSet;
 
-    use crate::qmc::{Imp, Tri};
+    use crate::qmc::{Cube, Imp, ImpMax, Invalid, Tri};
+
+    /// minterms of `vars` inputs by index, input 0 being the least significant bit
+    pub(crate) fn minterms(vars: usize, terms: &[usize]) -> HashSet<Imp> {
+        terms
+            .iter()
+            .map(|m| {
+                Imp((0..vars)
+                    .map(|i| if (m >> i) & 1 == 1 { Tri::T } else { Tri::F })
+                    .collect())
+            })
+            .collect()
+    }
+
+    /// `a | b` over two inputs
+    pub(crate) fn or() -> HashSet<Imp> {
+        minterms(2, &[1, 2, 3])
+    }
+
+    /// the cyclic function of minterms 0, 1, 2, 5, 6 and 7, which has no
+    /// essential prime
+    pub(crate) fn cyclic() -> HashSet<Imp> {
+        minterms(3, &[0, 1, 2, 5, 6, 7])
+    }
 
     #[test]
     fn basic() {
@@ -191,4 +625,114 @@ mod test {
 
         assert_eq!(super::reduce(&mset), mred);
     }
+
+    #[test]
+    fn algebraic() {
+        let names = ["a", "b", "c"].map(String::from);
+        let m = Imp(vec![Tri::F, Tri::T, Tri::X]);
+        assert_eq!(m.product(&names), "a' b");
+        assert_eq!(m.index(), None);
+        assert_eq!(ImpMax::from(m).sum(&names), "(a + b')");
+        let m1 = Imp(vec![Tri::T, Tri::F, Tri::F]);
+        let m6 = Imp(vec![Tri::F, Tri::T, Tri::T]);
+        assert_eq!((m1.index(), m6.index()), (Some(1), Some(6)));
+        let terms = HashSet::from([m6, m1]);
+        assert_eq!(super::indices(&terms, false), "Σm(1,6)");
+        assert_eq!(super::indices(&terms, true), "ΠM(1,6)");
+    }
+
+    #[test]
+    fn cube() {
+        let m0 = Imp(vec![Tri::F, Tri::T, Tri::F, Tri::F]);
+        let m1 = Imp(vec![Tri::T, Tri::T, Tri::F, Tri::F]);
+        let m2 = Imp(vec![Tri::X, Tri::T, Tri::F, Tri::F]);
+        let m3 = Imp(vec![Tri::X, Tri::F, Tri::F, Tri::F]);
+        let m4 = Imp(vec![Tri::X, Tri::X, Tri::F, Tri::F]);
+        let [c0, c1, c2, c3, c4] = [&m0, &m1, &m2, &m3, &m4].map(Cube::from);
+        assert_eq!(c0.merge(&c1), Some(c2.clone()));
+        assert_eq!(c0.merge(&c2), None);
+        assert_eq!(c2.merge(&c3), Some(c4.clone()));
+        assert!(c2.contains(&c0));
+        assert!(c2.contains(&c1));
+        assert!(!c3.contains(&c2));
+        assert_eq!((c2.ones(), c2.literals()), (1, 3));
+        assert_eq!(Imp::from(&c4), m4);
+
+        // cubes wider than a single word
+        let mut wide = vec![Tri::X; 130];
+        wide[0] = Tri::T;
+        wide[129] = Tri::F;
+        let mut other = wide.clone();
+        other[129] = Tri::T;
+        let (w0, w1) = (Cube::from(&Imp(wide.clone())), Cube::from(&Imp(other)));
+        wide[129] = Tri::X;
+        assert_eq!(w0.merge(&w1).map(|c| Imp::from(&c)), Some(Imp(wide)));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn par_reduce() {
+        // pseudo random 12 input function
+        let mut rng = crate::testing::Rng::new(0x2545f4914f6cdd1d);
+        let terms: Vec<usize> = (0..1 << 12).filter(|_| rng.next_u64() & 3 != 0).collect();
+        let mset = minterms(12, &terms);
+        assert_eq!(super::par_reduce(&mset), super::reduce(&mset));
+    }
+
+    #[test]
+    fn verify() {
+        // a | b over two inputs
+        let f = |x: &[bool]| x[0] | x[1];
+        let a = Imp(vec![Tri::T, Tri::X]);
+        let b = Imp(vec![Tri::X, Tri::T]);
+        let ab = Imp(vec![Tri::T, Tri::T]);
+        let nab = Imp(vec![Tri::F, Tri::T]);
+        let all = Imp(vec![Tri::X, Tri::X]);
+        assert_eq!(
+            super::verify(&HashSet::from([a.clone(), b.clone()]), 2, f),
+            Ok(())
+        );
+        assert_eq!(
+            super::verify(&HashSet::from([a.clone()]), 2, f),
+            Err(Invalid::Uncovered(nab.clone()))
+        );
+        assert_eq!(
+            super::verify(&HashSet::from([all.clone()]), 2, f),
+            Err(Invalid::Overcovered(all))
+        );
+        assert_eq!(
+            super::verify(&HashSet::from([a.clone(), nab.clone()]), 2, f),
+            Err(Invalid::NotPrime(nab))
+        );
+        assert_eq!(
+            super::verify(&HashSet::from([ab.clone(), a.clone(), b.clone()]), 2, f),
+            Err(Invalid::NotPrime(ab))
+        );
+        // every minterm is a cube that is not prime, the first one is reported
+        for _ in 0..8 {
+            assert_eq!(
+                super::verify(&or(), 2, f),
+                Err(Invalid::NotPrime(Imp(vec![Tri::F, Tri::T])))
+            );
+        }
+
+        let chosen = super::cover(&or(), &super::reduce(&or()));
+        assert_eq!(chosen, HashSet::from([a, b]));
+        assert_eq!(super::verify(&chosen, 2, f), Ok(()));
+    }
+
+    #[test]
+    fn deterministic() {
+        // the cyclic function needs fallback choices, which must not depend
+        // on the iteration order of the sets
+        let steps = super::cover_steps(&cyclic(), &super::reduce(&cyclic()));
+        assert!(!steps[0].essential);
+        for _ in 0..8 {
+            let m = cyclic();
+            assert_eq!(super::cover_steps(&m, &super::reduce(&m)), steps);
+        }
+        let mut sorted = super::sorted(&cyclic()).into_iter().map(|m| m.to_string());
+        assert_eq!(sorted.next().as_deref(), Some("000"));
+        assert_eq!(sorted.next().as_deref(), Some("010"));
+    }
 }
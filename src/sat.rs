@@ -0,0 +1,682 @@
+use crate::aig::{topological, And, Const, Inv, Sym, AIG};
+use std::collections::HashMap;
+
+/// Literal of variable `var`, as `2 * var + neg` like AIGER
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, PartialOrd, Ord)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub fn new(var: usize, neg: bool) -> Self {
+        Self(((var as u32) << 1) | neg as u32)
+    }
+    pub fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+    pub fn is_neg(self) -> bool {
+        (self.0 & 1) == 1
+    }
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl std::ops::Not for Lit {
+    type Output = Self;
+    fn not(self) -> Self::Output {
+        Self(self.0 ^ 1)
+    }
+}
+
+/// Tseitin encoding of AIGs, input `i` is variable `i`
+pub struct Cnf {
+    pub vars: usize,
+    pub clauses: Vec<Vec<Lit>>,
+}
+
+impl Cnf {
+    pub fn new(inputs: usize) -> Self {
+        Self {
+            vars: inputs,
+            clauses: vec![],
+        }
+    }
+
+    pub fn new_var(&mut self) -> usize {
+        self.vars += 1;
+        self.vars - 1
+    }
+
+    /// literals equal to `outputs`, shared nodes are encoded once
+    pub fn encode(&mut self, outputs: &[Box<AIG>]) -> Vec<Lit> {
+        let mut memo: HashMap<*const AIG, Lit> = HashMap::new();
+        let lit = |memo: &HashMap<*const AIG, Lit>, aig: &AIG| memo[&(aig as *const AIG)];
+        for aig in topological(outputs.iter().map(|o| &**o)) {
+            let l = match aig {
+                AIG::A(And(a, b)) => {
+                    let (a, b) = (lit(&memo, a), lit(&memo, b));
+                    let x = Lit::new(self.new_var(), false);
+                    // x <-> a & b
+                    self.clauses.push(vec![!x, a]);
+                    self.clauses.push(vec![!x, b]);
+                    self.clauses.push(vec![x, !a, !b]);
+                    x
+                }
+                AIG::I(Inv(r)) => !lit(&memo, r),
+                AIG::G(Sym(i)) => Lit::new(*i, false),
+                AIG::C(Const(v)) => {
+                    let x = Lit::new(self.new_var(), false);
+                    self.clauses.push(vec![if *v { x } else { !x }]);
+                    x
+                }
+            };
+            memo.insert(aig as *const AIG, l);
+        }
+        outputs.iter().map(|o| lit(&memo, o)).collect()
+    }
+}
+
+/// Max heap of variables ordered by activity
+#[derive(Default)]
+struct Heap {
+    heap: Vec<usize>,
+    /// position of each variable in `heap`
+    index: Vec<Option<usize>>,
+}
+
+impl Heap {
+    fn contains(&self, var: usize) -> bool {
+        self.index[var].is_some()
+    }
+
+    fn up(&mut self, mut i: usize, activity: &[f64]) {
+        let var = self.heap[i];
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if activity[self.heap[parent]] >= activity[var] {
+                break;
+            }
+            self.heap[i] = self.heap[parent];
+            self.index[self.heap[i]] = Some(i);
+            i = parent;
+        }
+        self.heap[i] = var;
+        self.index[var] = Some(i);
+    }
+
+    fn down(&mut self, mut i: usize, activity: &[f64]) {
+        let var = self.heap[i];
+        loop {
+            let mut child = 2 * i + 1;
+            if child >= self.heap.len() {
+                break;
+            }
+            if child + 1 < self.heap.len()
+                && activity[self.heap[child + 1]] > activity[self.heap[child]]
+            {
+                child += 1;
+            }
+            if activity[self.heap[child]] <= activity[var] {
+                break;
+            }
+            self.heap[i] = self.heap[child];
+            self.index[self.heap[i]] = Some(i);
+            i = child;
+        }
+        self.heap[i] = var;
+        self.index[var] = Some(i);
+    }
+
+    fn insert(&mut self, var: usize, activity: &[f64]) {
+        if self.index.len() <= var {
+            self.index.resize(var + 1, None);
+        }
+        if self.contains(var) {
+            return;
+        }
+        self.heap.push(var);
+        self.up(self.heap.len() - 1, activity);
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        let var = *self.heap.first()?;
+        let last = self.heap.pop().unwrap();
+        self.index[var] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.down(0, activity);
+        }
+        Some(var)
+    }
+}
+
+struct Clause {
+    lits: Vec<Lit>,
+    learnt: bool,
+    activity: f64,
+    deleted: bool,
+}
+
+/// `i`-th element (from 0) of the Luby sequence 1, 1, 2, 1, 1, 2, 4, ...
+fn luby(mut i: u64) -> u64 {
+    let (mut size, mut seq) = (1, 0);
+    while size < i + 1 {
+        seq += 1;
+        size = 2 * size + 1;
+    }
+    while size - 1 != i {
+        size = (size - 1) / 2;
+        seq -= 1;
+        i %= size;
+    }
+    1 << seq
+}
+
+/// CDCL SAT solver with two watched literals, VSIDS, first UIP clause
+/// learning, phase saving and Luby restarts
+pub struct Solver {
+    clauses: Vec<Clause>,
+    learnts: Vec<usize>,
+    /// clauses watching each literal, visited when the literal becomes false
+    watches: Vec<Vec<usize>>,
+    assigns: Vec<Option<bool>>,
+    level: Vec<usize>,
+    reason: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    trail_lim: Vec<usize>,
+    qhead: usize,
+    activity: Vec<f64>,
+    var_inc: f64,
+    cla_inc: f64,
+    order: Heap,
+    phase: Vec<bool>,
+    seen: Vec<bool>,
+    model: Vec<bool>,
+    max_learnts: f64,
+    /// false once the clauses are unsatisfiable without assumptions
+    ok: bool,
+    pub conflicts: u64,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&Cnf> for Solver {
+    fn from(cnf: &Cnf) -> Self {
+        let mut solver = Self::new();
+        solver.reserve(cnf.vars);
+        for clause in &cnf.clauses {
+            solver.add_clause(clause);
+        }
+        solver
+    }
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Self {
+            clauses: vec![],
+            learnts: vec![],
+            watches: vec![],
+            assigns: vec![],
+            level: vec![],
+            reason: vec![],
+            trail: vec![],
+            trail_lim: vec![],
+            qhead: 0,
+            activity: vec![],
+            var_inc: 1.0,
+            cla_inc: 1.0,
+            order: Heap::default(),
+            phase: vec![],
+            seen: vec![],
+            model: vec![],
+            max_learnts: 0.0,
+            ok: true,
+            conflicts: 0,
+        }
+    }
+
+    pub fn vars(&self) -> usize {
+        self.assigns.len()
+    }
+
+    /// make sure variables `0..vars` exist
+    pub fn reserve(&mut self, vars: usize) {
+        while self.vars() < vars {
+            let var = self.vars();
+            self.watches.push(vec![]);
+            self.watches.push(vec![]);
+            self.assigns.push(None);
+            self.level.push(0);
+            self.reason.push(None);
+            self.activity.push(0.0);
+            self.phase.push(false);
+            self.seen.push(false);
+            self.order.insert(var, &self.activity);
+        }
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.assigns[lit.var()].map(|v| v ^ lit.is_neg())
+    }
+
+    fn decision_level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        self.assigns[lit.var()] = Some(!lit.is_neg());
+        self.level[lit.var()] = self.decision_level();
+        self.reason[lit.var()] = reason;
+        self.trail.push(lit);
+    }
+
+    fn attach(&mut self, lits: Vec<Lit>, learnt: bool) -> usize {
+        let c = self.clauses.len();
+        self.watches[lits[0].index()].push(c);
+        self.watches[lits[1].index()].push(c);
+        self.clauses.push(Clause {
+            lits,
+            learnt,
+            activity: 0.0,
+            deleted: false,
+        });
+        c
+    }
+
+    /// add a clause at decision level 0, false once the clauses are unsatisfiable
+    pub fn add_clause(&mut self, lits: &[Lit]) -> bool {
+        assert_eq!(self.decision_level(), 0);
+        if !self.ok {
+            return false;
+        }
+        if let Some(var) = lits.iter().map(|l| l.var()).max() {
+            self.reserve(var + 1);
+        }
+        let mut lits = lits.to_vec();
+        lits.sort();
+        lits.dedup();
+        let mut clause = vec![];
+        for (i, l) in lits.iter().enumerate() {
+            if self.value(*l) == Some(true) || (i > 0 && lits[i - 1] == !*l) {
+                // satisfied or tautology
+                return true;
+            }
+            if self.value(*l).is_none() {
+                clause.push(*l);
+            }
+        }
+        match clause.len() {
+            0 => self.ok = false,
+            1 => {
+                self.enqueue(clause[0], None);
+                self.ok = self.propagate().is_none();
+            }
+            _ => {
+                self.attach(clause, false);
+            }
+        }
+        self.ok
+    }
+
+    /// unit propagation, returns a conflicting clause
+    fn propagate(&mut self) -> Option<usize> {
+        while self.qhead < self.trail.len() {
+            let p = self.trail[self.qhead];
+            self.qhead += 1;
+            let false_lit = !p;
+            let watchers = std::mem::take(&mut self.watches[false_lit.index()]);
+            let mut kept = Vec::with_capacity(watchers.len());
+            let mut conflict = None;
+            for (i, &c) in watchers.iter().enumerate() {
+                if conflict.is_some() {
+                    kept.extend_from_slice(&watchers[i..]);
+                    break;
+                }
+                if self.clauses[c].deleted {
+                    continue;
+                }
+                let lits = &mut self.clauses[c].lits;
+                if lits[0] == false_lit {
+                    lits.swap(0, 1);
+                }
+                let first = lits[0];
+                if self.assigns[first.var()].map(|v| v ^ first.is_neg()) == Some(true) {
+                    kept.push(c);
+                    continue;
+                }
+                // look for a new literal to watch
+                let mut moved = false;
+                for k in 2..lits.len() {
+                    let l = lits[k];
+                    if self.assigns[l.var()].map(|v| v ^ l.is_neg()) != Some(false) {
+                        lits.swap(1, k);
+                        self.watches[l.index()].push(c);
+                        moved = true;
+                        break;
+                    }
+                }
+                if moved {
+                    continue;
+                }
+                kept.push(c);
+                match self.value(first) {
+                    Some(false) => conflict = Some(c),
+                    _ => self.enqueue(first, Some(c)),
+                }
+            }
+            self.watches[false_lit.index()] = kept;
+            if conflict.is_some() {
+                self.qhead = self.trail.len();
+                return conflict;
+            }
+        }
+        None
+    }
+
+    fn bump_var(&mut self, var: usize) {
+        self.activity[var] += self.var_inc;
+        if self.activity[var] > 1e100 {
+            for a in &mut self.activity {
+                *a *= 1e-100;
+            }
+            self.var_inc *= 1e-100;
+        }
+        if let Some(i) = self.order.index.get(var).copied().flatten() {
+            self.order.up(i, &self.activity);
+        }
+    }
+
+    fn bump_clause(&mut self, c: usize) {
+        self.clauses[c].activity += self.cla_inc;
+        if self.clauses[c].activity > 1e20 {
+            for l in &self.learnts {
+                self.clauses[*l].activity *= 1e-20;
+            }
+            self.cla_inc *= 1e-20;
+        }
+    }
+
+    /// first UIP learnt clause, asserting literal first, and the level to backtrack to
+    fn analyze(&mut self, mut confl: usize) -> (Vec<Lit>, usize) {
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut p: Option<Lit> = None;
+        let mut index = self.trail.len();
+
+        loop {
+            if self.clauses[confl].learnt {
+                self.bump_clause(confl);
+            }
+            let skip = p.is_some() as usize;
+            for j in skip..self.clauses[confl].lits.len() {
+                let q = self.clauses[confl].lits[j];
+                let var = q.var();
+                if !self.seen[var] && self.level[var] > 0 {
+                    self.bump_var(var);
+                    self.seen[var] = true;
+                    if self.level[var] >= self.decision_level() {
+                        pending += 1;
+                    } else {
+                        learnt.push(q);
+                    }
+                }
+            }
+            // next literal of the current level on the trail
+            loop {
+                index -= 1;
+                if self.seen[self.trail[index].var()] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            p = Some(lit);
+            self.seen[lit.var()] = false;
+            pending -= 1;
+            if pending == 0 {
+                break;
+            }
+            confl = self.reason[lit.var()].unwrap();
+        }
+        learnt[0] = !p.unwrap();
+
+        for l in &learnt[1..] {
+            self.seen[l.var()] = false;
+        }
+
+        // literal of the highest remaining level goes second, to be watched
+        let mut level = 0;
+        if learnt.len() > 1 {
+            let max = (1..learnt.len())
+                .max_by_key(|i| self.level[learnt[*i].var()])
+                .unwrap();
+            learnt.swap(1, max);
+            level = self.level[learnt[1].var()];
+        }
+        (learnt, level)
+    }
+
+    fn cancel_until(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return;
+        }
+        for l in self.trail.drain(self.trail_lim[level]..).rev() {
+            self.assigns[l.var()] = None;
+            self.reason[l.var()] = None;
+            self.phase[l.var()] = !l.is_neg();
+            self.order.insert(l.var(), &self.activity);
+        }
+        self.trail_lim.truncate(level);
+        self.qhead = self.trail.len();
+    }
+
+    /// drop the less active half of the learnt clauses that are not reasons
+    fn reduce_db(&mut self) {
+        let mut learnts = std::mem::take(&mut self.learnts);
+        learnts.sort_by(|a, b| {
+            self.clauses[*a]
+                .activity
+                .total_cmp(&self.clauses[*b].activity)
+        });
+        let half = learnts.len() / 2;
+        for (i, c) in learnts.into_iter().enumerate() {
+            let first = self.clauses[c].lits[0];
+            let locked = self.reason[first.var()] == Some(c) && self.value(first) == Some(true);
+            if i < half && !locked && self.clauses[c].lits.len() > 2 {
+                self.clauses[c].deleted = true;
+                self.clauses[c].lits = vec![];
+            } else {
+                self.learnts.push(c);
+            }
+        }
+    }
+
+    fn pick_branch(&mut self) -> Option<Lit> {
+        while let Some(var) = self.order.pop(&self.activity) {
+            if self.assigns[var].is_none() {
+                return Some(Lit::new(var, !self.phase[var]));
+            }
+        }
+        None
+    }
+
+    /// CDCL until `limit` conflicts, `None` when the limit is hit
+    fn search(&mut self, limit: u64, assumptions: &[Lit]) -> Option<bool> {
+        let mut conflicts = 0;
+        loop {
+            if let Some(confl) = self.propagate() {
+                self.conflicts += 1;
+                conflicts += 1;
+                if self.decision_level() == 0 {
+                    self.ok = false;
+                    return Some(false);
+                }
+                let (learnt, level) = self.analyze(confl);
+                self.cancel_until(level);
+                if learnt.len() == 1 {
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let first = learnt[0];
+                    let c = self.attach(learnt, true);
+                    self.learnts.push(c);
+                    self.bump_clause(c);
+                    self.enqueue(first, Some(c));
+                }
+                self.var_inc /= 0.95;
+                self.cla_inc /= 0.999;
+                continue;
+            }
+
+            if conflicts >= limit {
+                self.cancel_until(0);
+                return None;
+            }
+            if self.learnts.len() as f64 >= self.max_learnts + self.trail.len() as f64 {
+                self.reduce_db();
+            }
+
+            let mut next = None;
+            while self.decision_level() < assumptions.len() {
+                let p = assumptions[self.decision_level()];
+                match self.value(p) {
+                    // already implied, open an empty level for it
+                    Some(true) => self.trail_lim.push(self.trail.len()),
+                    Some(false) => return Some(false),
+                    None => {
+                        next = Some(p);
+                        break;
+                    }
+                }
+            }
+            let next = match next.or_else(|| self.pick_branch()) {
+                Some(l) => l,
+                None => return Some(true),
+            };
+            self.trail_lim.push(self.trail.len());
+            self.enqueue(next, None);
+        }
+    }
+
+    /// satisfiability of the clauses together with `assumptions`
+    pub fn solve(&mut self, assumptions: &[Lit]) -> bool {
+        if !self.ok {
+            return false;
+        }
+        if let Some(var) = assumptions.iter().map(|l| l.var()).max() {
+            self.reserve(var + 1);
+        }
+        self.max_learnts = f64::max(self.clauses.len() as f64 / 3.0, 1000.0);
+        let mut restarts = 0;
+        let result = loop {
+            if let Some(result) = self.search(luby(restarts) * 100, assumptions) {
+                break result;
+            }
+            restarts += 1;
+            self.max_learnts *= 1.1;
+        };
+        if result {
+            self.model = self.assigns.iter().map(|v| v.unwrap()).collect();
+        }
+        self.cancel_until(0);
+        result
+    }
+
+    /// value of `var` in the last satisfying assignment
+    pub fn model(&self, var: usize) -> bool {
+        self.model[var]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{luby, Cnf, Lit, Solver};
+    use crate::aig::{miter, AIG};
+
+    #[test]
+    fn luby_sequence() {
+        let seq: Vec<_> = (0..15).map(luby).collect();
+        assert_eq!(seq, vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn pigeonhole() {
+        // 5 pigeons in 4 holes, p * 4 + h is pigeon p in hole h
+        let mut s = Solver::new();
+        for p in 0..5 {
+            s.add_clause(
+                &(0..4)
+                    .map(|h| Lit::new(p * 4 + h, false))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        for h in 0..4 {
+            for p in 0..5 {
+                for q in p + 1..5 {
+                    s.add_clause(&[Lit::new(p * 4 + h, true), Lit::new(q * 4 + h, true)]);
+                }
+            }
+        }
+        assert!(!s.solve(&[]));
+    }
+
+    #[test]
+    fn random() {
+        // random 3-SAT near the threshold against brute force
+        let mut rng = crate::testing::Rng::new(0x9e3779b97f4a7c15);
+        let mut next = |n: u64| rng.below(n);
+        for _ in 0..50 {
+            let vars = 10;
+            let clauses: Vec<Vec<Lit>> = (0..43)
+                .map(|_| {
+                    (0..3)
+                        .map(|_| Lit::new(next(vars) as usize, next(2) == 1))
+                        .collect()
+                })
+                .collect();
+            let sat = |m: u64| {
+                clauses
+                    .iter()
+                    .all(|c| c.iter().any(|l| ((m >> l.var()) & 1 == 1) != l.is_neg()))
+            };
+            let expected = (0..1 << vars).any(sat);
+            let mut s = Solver::new();
+            for c in &clauses {
+                s.add_clause(c);
+            }
+            assert_eq!(s.solve(&[]), expected);
+            if expected {
+                let m = (0..vars as usize).fold(0, |m, v| m | ((s.model(v) as u64) << v));
+                assert!(sat(m));
+            }
+        }
+    }
+
+    #[test]
+    fn miters() {
+        let (_, adder) = crate::aag::parse(include_bytes!("../verilog/adder.aag")).unwrap();
+        let (_, pla) = crate::pla::parse(include_bytes!("../verilog/adder.pla")).unwrap();
+        let (_, popcount) = crate::aag::parse(include_bytes!("../verilog/popcount.aag")).unwrap();
+
+        let mut cnf = Cnf::new(4);
+        let m = cnf.encode(&[miter(&adder, &pla), miter(&adder, &popcount)]);
+        let mut s = Solver::from(&cnf);
+        assert!(!s.solve(&[m[0]]));
+        assert!(s.solve(&[m[1]]));
+        let input: Vec<_> = (0..4).map(|v| s.model(v)).collect();
+        assert!(adder
+            .iter()
+            .zip(&popcount)
+            .any(|(a, p)| a.eval(&input) != p.eval(&input)));
+
+        // a | !a is a tautology
+        let a: Box<AIG> = 0.into();
+        let mut cnf = Cnf::new(1);
+        let t = cnf.encode(&[a.clone() | !a])[0];
+        let mut s = Solver::from(&cnf);
+        assert!(s.solve(&[t]));
+        assert!(!s.solve(&[!t]));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::opt::{inverted, node, Graph, Lit};
+use std::{collections::BTreeMap, fmt::Display};
+
+/// Size metrics of the cone of some outputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// inputs in the cone, the structural support
+    pub support: usize,
+    pub ands: usize,
+    /// distinct complemented literals used by AND nodes and outputs
+    pub inverters: usize,
+    pub levels: usize,
+    /// number of inputs and AND nodes by their number of fanouts
+    pub fanouts: BTreeMap<usize, usize>,
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "support {}, {} AND nodes, {} inverters, {} levels, fanouts",
+            self.support, self.ands, self.inverters, self.levels
+        )?;
+        for (fanout, count) in &self.fanouts {
+            write!(f, " {}:{}", fanout, count)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn stats(g: &Graph, roots: &[Lit]) -> Stats {
+    let mut refs = vec![0; g.len()];
+    let mut inverted_lits = vec![false; 2 * g.len()];
+    let mut used = |l: Lit, refs: &mut [usize]| {
+        refs[node(l)] += 1;
+        if inverted(l) && node(l) != 0 {
+            inverted_lits[l as usize] = true;
+        }
+    };
+    for r in roots {
+        used(*r, &mut refs);
+    }
+    for n in (g.inputs + 1..g.len()).rev() {
+        if refs[n] > 0 {
+            let (a, b) = g.fanins(n);
+            used(a, &mut refs);
+            used(b, &mut refs);
+        }
+    }
+
+    let levels = g.levels();
+    let mut fanouts = BTreeMap::new();
+    for r in refs.iter().skip(1).filter(|r| **r > 0) {
+        *fanouts.entry(*r).or_default() += 1;
+    }
+    Stats {
+        support: (1..=g.inputs).filter(|n| refs[*n] > 0).count(),
+        ands: (g.inputs + 1..g.len()).filter(|n| refs[*n] > 0).count(),
+        inverters: inverted_lits.iter().filter(|i| **i).count(),
+        levels: roots.iter().map(|r| levels[node(*r)]).max().unwrap_or(0),
+        fanouts,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::stats;
+    use crate::opt::Graph;
+
+    #[test]
+    fn basic() {
+        // !(a & b) and (a & b) & !c
+        let mut g = Graph::new(3);
+        let (a, b, c) = (g.input(0), g.input(1), g.input(2));
+        let ab = g.and(a, b);
+        let f = g.and(ab, c ^ 1);
+        let s = stats(&g, &[ab ^ 1, f]);
+        assert_eq!((s.support, s.ands, s.inverters, s.levels), (3, 2, 2, 2));
+        assert_eq!(s.fanouts.into_iter().collect::<Vec<_>>(), [(1, 4), (2, 1)]);
+        let s = stats(&g, &[ab ^ 1]);
+        assert_eq!((s.support, s.ands, s.inverters, s.levels), (2, 1, 1, 1));
+        assert_eq!(
+            stats(&g, &[0]).to_string(),
+            "support 0, 0 AND nodes, 0 inverters, 0 levels, fanouts"
+        );
+    }
+}
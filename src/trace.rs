@@ -0,0 +1,336 @@
+use crate::qmc::{cover_steps, sorted, Imp, Step, Tri};
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
+
+/// Merge round of Quine-McCluskey
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Round {
+    /// cubes of the round by number of ones
+    pub groups: Vec<Vec<Imp>>,
+    /// cubes of adjacent groups and their merge
+    pub merges: Vec<(Imp, Imp, Imp)>,
+    /// cubes merged at least once
+    pub used: Vec<Imp>,
+    /// cubes never merged, which are prime
+    pub primes: Vec<Imp>,
+}
+
+/// Prime implicant chart, `marks[p][m]` being set when prime `p` contains
+/// minterm `m`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chart {
+    /// input of every position of the cubes, the support of the function
+    pub inputs: Vec<usize>,
+    pub primes: Vec<Imp>,
+    pub minterms: Vec<Imp>,
+    pub marks: Vec<Vec<bool>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub rounds: Vec<Round>,
+    pub chart: Chart,
+    pub steps: Vec<Step>,
+}
+
+fn ones(imp: &Imp) -> usize {
+    imp.0.iter().filter(|t| **t == Tri::T).count()
+}
+
+impl Chart {
+    /// chart of cubes over `inputs`
+    pub fn new(minterms: &HashSet<Imp>, primes: &HashSet<Imp>, inputs: &[usize]) -> Self {
+        let (minterms, primes) = (sorted(minterms), sorted(primes));
+        let marks = primes
+            .iter()
+            .map(|p| minterms.iter().map(|m| p.containes(m)).collect())
+            .collect();
+        Self {
+            inputs: inputs.to_vec(),
+            primes,
+            minterms,
+            marks,
+        }
+    }
+
+    fn over(&self) -> String {
+        let inputs: Vec<String> = self.inputs.iter().map(usize::to_string).collect();
+        format!("over inputs {}", inputs.join(" "))
+    }
+
+    /// the inputs of the cubes, then one row per prime, `x` marking the
+    /// minterms it contains
+    pub fn text(&self) -> String {
+        let width = self.inputs.len().max(1);
+        let mut out = String::new();
+        writeln!(out, "{}", self.over()).unwrap();
+        write!(out, "{:width$}", "").unwrap();
+        for m in &self.minterms {
+            write!(out, " {}", m).unwrap();
+        }
+        writeln!(out).unwrap();
+        for (p, row) in self.primes.iter().zip(&self.marks) {
+            write!(out, "{}", p).unwrap();
+            for mark in row {
+                write!(out, " {:>width$}", if *mark { "x" } else { "." }).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+        out
+    }
+
+    /// primes that are the only one containing some minterm
+    pub fn essentials(&self) -> Vec<&Imp> {
+        self.primes
+            .iter()
+            .enumerate()
+            .filter(|(p, _)| {
+                (0..self.minterms.len())
+                    .any(|m| self.marks[*p][m] && self.marks.iter().filter(|r| r[m]).count() == 1)
+            })
+            .map(|(_, p)| p)
+            .collect()
+    }
+
+    /// header of the minterms, then a row of 0 and 1 per prime
+    pub fn csv(&self) -> String {
+        let mut out = format!("prime {}", self.over());
+        for m in &self.minterms {
+            write!(out, ",{}", m).unwrap();
+        }
+        writeln!(out).unwrap();
+        for (p, row) in self.primes.iter().zip(&self.marks) {
+            write!(out, "{}", p).unwrap();
+            for mark in row {
+                write!(out, ",{}", *mark as u8).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+        out
+    }
+
+    pub fn json(&self) -> String {
+        let marks: Vec<String> = self
+            .marks
+            .iter()
+            .map(|row| format!("[{}]", join(row.iter().map(bool::to_string))))
+            .collect();
+        format!(
+            "{{\"inputs\":[{}],\"primes\":{},\"minterms\":{},\"marks\":[{}]}}",
+            join(self.inputs.iter().map(usize::to_string)),
+            strings(&self.primes),
+            strings(&self.minterms),
+            marks.join(",")
+        )
+    }
+}
+
+fn join(items: impl Iterator<Item = String>) -> String {
+    items.collect::<Vec<_>>().join(",")
+}
+
+/// JSON array of cubes, which never need escaping
+fn strings(imps: &[Imp]) -> String {
+    format!("[{}]", join(imps.iter().map(|i| format!("\"{}\"", i))))
+}
+
+fn cubes(imps: &[Imp]) -> String {
+    if imps.is_empty() {
+        return "none".to_string();
+    }
+    imps.iter()
+        .map(Imp::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Run Quine-McCluskey on `minterms` step by step, recording every merge
+/// round, the prime implicant chart and the choices covering it
+pub fn trace(minterms: &HashSet<Imp>) -> Trace {
+    let mut rounds = vec![];
+    let mut all = HashSet::new();
+    let mut curr: BTreeSet<Imp> = minterms.iter().cloned().collect();
+    while !curr.is_empty() {
+        let mut groups: Vec<Vec<Imp>> = vec![];
+        for imp in &curr {
+            let ones = ones(imp);
+            if groups.len() <= ones {
+                groups.resize(ones + 1, vec![]);
+            }
+            groups[ones].push(imp.clone());
+        }
+        let mut merges = vec![];
+        let mut used = BTreeSet::new();
+        let mut next = BTreeSet::new();
+        for w in groups.windows(2) {
+            for a in &w[0] {
+                for b in &w[1] {
+                    if let Some(m) = a.merge(b) {
+                        used.insert(a.clone());
+                        used.insert(b.clone());
+                        next.insert(m.clone());
+                        merges.push((a.clone(), b.clone(), m));
+                    }
+                }
+            }
+        }
+        let primes: Vec<Imp> = curr.difference(&used).cloned().collect();
+        all.extend(primes.iter().cloned());
+        rounds.push(Round {
+            groups,
+            merges,
+            used: used.into_iter().collect(),
+            primes,
+        });
+        curr = next;
+    }
+    let inputs: Vec<usize> = (0..minterms.iter().next().map_or(0, |m| m.0.len())).collect();
+    Trace {
+        rounds,
+        chart: Chart::new(minterms, &all, &inputs),
+        steps: cover_steps(minterms, &all),
+    }
+}
+
+impl Trace {
+    pub fn text(&self) -> String {
+        let mut out = String::new();
+        for (r, round) in self.rounds.iter().enumerate() {
+            writeln!(out, "round {}:", r + 1).unwrap();
+            for (ones, group) in round.groups.iter().enumerate() {
+                writeln!(out, "  group {}: {}", ones, cubes(group)).unwrap();
+            }
+            for (a, b, m) in &round.merges {
+                writeln!(out, "  merged {} and {} into {}", a, b, m).unwrap();
+            }
+            writeln!(out, "  used: {}", cubes(&round.used)).unwrap();
+            writeln!(out, "  primes: {}", cubes(&round.primes)).unwrap();
+        }
+        writeln!(out, "prime implicant chart:").unwrap();
+        out.push_str(&self.chart.text());
+        for step in &self.steps {
+            writeln!(
+                out,
+                "{} {} for {}, covering {}",
+                if step.essential { "essential" } else { "chose" },
+                step.prime,
+                step.minterm,
+                cubes(&step.covered)
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    pub fn json(&self) -> String {
+        let rounds: Vec<String> = self
+            .rounds
+            .iter()
+            .map(|r| {
+                let merges = join(
+                    r.merges
+                        .iter()
+                        .map(|(a, b, m)| strings(&[a.clone(), b.clone(), m.clone()])),
+                );
+                format!(
+                    "{{\"groups\":[{}],\"merges\":[{}],\"used\":{},\"primes\":{}}}",
+                    join(r.groups.iter().map(|g| strings(g))),
+                    merges,
+                    strings(&r.used),
+                    strings(&r.primes)
+                )
+            })
+            .collect();
+        let steps = join(self.steps.iter().map(|s| {
+            format!(
+                "{{\"prime\":\"{}\",\"minterm\":\"{}\",\"essential\":{},\"covered\":{}}}",
+                s.prime,
+                s.minterm,
+                s.essential,
+                strings(&s.covered)
+            )
+        }));
+        format!(
+            "{{\"rounds\":[{}],\"chart\":{},\"steps\":[{}]}}",
+            rounds.join(","),
+            self.chart.json(),
+            steps
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{trace, Chart};
+    use crate::qmc::{
+        reduce,
+        test::{cyclic, or},
+        Imp,
+    };
+    use std::collections::HashSet;
+
+    #[test]
+    fn rounds() {
+        let t = trace(&or());
+        assert_eq!(t.rounds.len(), 2);
+        assert_eq!(t.rounds[0].groups[1].len(), 2);
+        assert_eq!(t.rounds[0].merges.len(), 2);
+        assert_eq!(t.rounds[0].used.len(), 3);
+        assert!(t.rounds[0].primes.is_empty());
+        let primes: HashSet<Imp> = t.rounds[1].primes.iter().cloned().collect();
+        assert_eq!(primes, reduce(&or()));
+    }
+
+    #[test]
+    fn chart() {
+        let t = trace(&or());
+        assert_eq!(t.chart.marks, [[false, true, true], [true, false, true]]);
+        assert_eq!(t.chart.essentials().len(), 2);
+        assert_eq!(
+            t.chart.csv(),
+            "prime over inputs 0 1,01,10,11\n1-,0,1,1\n-1,1,0,1\n"
+        );
+        let chart = Chart::new(&cyclic(), &reduce(&cyclic()), &[0, 1, 2]);
+        assert_eq!(chart.primes.len(), 6);
+        assert!(chart.essentials().is_empty());
+    }
+
+    #[test]
+    fn steps() {
+        let t = trace(&or());
+        assert_eq!(t.steps.len(), 2);
+        assert!(t.steps.iter().all(|s| s.essential));
+        let covered: usize = t.steps.iter().map(|s| s.covered.len()).sum();
+        assert_eq!(covered, 3);
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(
+            trace(&or()).text(),
+            "round 1:\n  group 0: none\n  group 1: 01 10\n  group 2: 11\n  \
+             merged 01 and 11 into -1\n  merged 10 and 11 into 1-\n  used: 01 10 11\n  \
+             primes: none\nround 2:\n  group 0: none\n  group 1: 1- -1\n  used: none\n  \
+             primes: 1- -1\nprime implicant chart:\nover inputs 0 1\n   01 10 11\n\
+             1-  .  x  x\n-1  x  .  x\nessential -1 for 01, covering 01 11\n\
+             essential 1- for 10, covering 10\n"
+        );
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            trace(&or()).json(),
+            "{\"rounds\":[{\"groups\":[[],[\"01\",\"10\"],[\"11\"]],\
+             \"merges\":[[\"01\",\"11\",\"-1\"],[\"10\",\"11\",\"1-\"]],\
+             \"used\":[\"01\",\"10\",\"11\"],\"primes\":[]},\
+             {\"groups\":[[],[\"1-\",\"-1\"]],\"merges\":[],\"used\":[],\"primes\":[\"1-\",\"-1\"]}],\
+             \"chart\":{\"inputs\":[0,1],\"primes\":[\"1-\",\"-1\"],\"minterms\":[\"01\",\"10\",\"11\"],\
+             \"marks\":[[false,true,true],[true,false,true]]},\
+             \"steps\":[{\"prime\":\"-1\",\"minterm\":\"01\",\"essential\":true,\"covered\":[\"01\",\"11\"]},\
+             {\"prime\":\"1-\",\"minterm\":\"10\",\"essential\":true,\"covered\":[\"10\"]}]}"
+        );
+    }
+}
@@ -0,0 +1,527 @@
+use crate::opt::VARS;
+use crate::qmc::{Imp, Tri};
+use std::{cmp::Reverse, fmt::Display, ops::Not};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Unateness {
+    /// raising the input never lowers the function
+    Positive,
+    /// raising the input never raises the function
+    Negative,
+    Binate,
+    Independent,
+}
+
+impl Display for Unateness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unateness::Positive => write!(f, "positive unate"),
+            Unateness::Negative => write!(f, "negative unate"),
+            Unateness::Binate => write!(f, "binate"),
+            Unateness::Independent => write!(f, "independent"),
+        }
+    }
+}
+
+/// NPN transform: negate the output if `output` and every input `v` with
+/// `phases[v]` set, then move input `perm[i]` to input `i`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Npn {
+    pub output: bool,
+    pub phases: Vec<bool>,
+    pub perm: Vec<usize>,
+}
+
+/// Widest function `Truth::npn` is meant for
+pub const NPN_VARS: usize = 8;
+
+/// Truth table of a function, bit `m` being its value under the minterm
+/// with input `i` set to bit `i` of `m`
+///
+/// Tables of fewer than 6 inputs repeat in a single word.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Truth {
+    vars: usize,
+    words: Vec<u64>,
+}
+
+impl Truth {
+    pub fn new(vars: usize, f: impl Fn(&[bool]) -> bool) -> Self {
+        let mut t = Self::zero(vars);
+        let mut input = vec![false; vars];
+        for m in 0..1_usize << vars {
+            for (i, x) in input.iter_mut().enumerate() {
+                *x = (m >> i) & 1 == 1;
+            }
+            if f(&input) {
+                t.set(m);
+            }
+        }
+        t
+    }
+
+    /// function that is true on the minterms covered by `imps`
+    pub fn from_imps<'a>(vars: usize, imps: impl IntoIterator<Item = &'a Imp>) -> Self {
+        let mut t = Self::zero(vars);
+        for imp in imps {
+            assert_eq!(imp.0.len(), vars);
+            let fixed = imp
+                .0
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (i, x)| acc | if *x == Tri::T { 1 << i } else { 0 });
+            let free: Vec<usize> = (0..vars).filter(|i| imp.0[*i] == Tri::X).collect();
+            for k in 0..1_usize << free.len() {
+                let m = free
+                    .iter()
+                    .enumerate()
+                    .fold(fixed, |acc, (j, i)| acc | (((k >> j) & 1) << i));
+                t.set(m);
+            }
+        }
+        t
+    }
+
+    fn zero(vars: usize) -> Self {
+        Self {
+            vars,
+            words: vec![0; 1 << vars.saturating_sub(6)],
+        }
+    }
+
+    fn set(&mut self, m: usize) {
+        if self.vars < 6 {
+            // repeat the table in the whole word
+            for k in (m..64).step_by(1 << self.vars) {
+                self.words[0] |= 1 << k;
+            }
+        } else {
+            self.words[m / 64] |= 1 << (m % 64);
+        }
+    }
+
+    pub fn vars(&self) -> usize {
+        self.vars
+    }
+
+    pub fn get(&self, m: usize) -> bool {
+        (self.words[m / 64] >> (m % 64)) & 1 == 1
+    }
+
+    /// number of minterms in the ON-Set
+    pub fn ones(&self) -> usize {
+        let ones: usize = self.words.iter().map(|w| w.count_ones() as usize).sum();
+        if self.vars < 6 {
+            ones >> (6 - self.vars)
+        } else {
+            ones
+        }
+    }
+
+    /// the function with input `var` fixed to `value`, as a function of the
+    /// same inputs that does not depend on `var`
+    pub fn cofactor(&self, var: usize, value: bool) -> Self {
+        assert!(var < self.vars);
+        let mut t = self.clone();
+        if var < 6 {
+            let s = 1 << var;
+            for w in &mut t.words {
+                *w = if value {
+                    (*w & VARS[var]) | ((*w & VARS[var]) >> s)
+                } else {
+                    (*w & !VARS[var]) | ((*w & !VARS[var]) << s)
+                };
+            }
+        } else {
+            let stride = 1 << (var - 6);
+            for i in 0..t.words.len() {
+                let from = if value { i | stride } else { i & !stride };
+                t.words[i] = self.words[from];
+            }
+        }
+        t
+    }
+
+    /// whether the function depends on input `var`
+    pub fn depends(&self, var: usize) -> bool {
+        self.cofactor(var, false) != self.cofactor(var, true)
+    }
+
+    /// whether every minterm of `self` is one of `other`
+    fn implies(&self, other: &Self) -> bool {
+        self.words
+            .iter()
+            .zip(&other.words)
+            .all(|(a, b)| a & !b == 0)
+    }
+
+    /// monotonicity in input `var`, from its two cofactors
+    pub fn unateness(&self, var: usize) -> Unateness {
+        let (f0, f1) = (self.cofactor(var, false), self.cofactor(var, true));
+        match (f0.implies(&f1), f1.implies(&f0)) {
+            (true, true) => Unateness::Independent,
+            (true, false) => Unateness::Positive,
+            (false, true) => Unateness::Negative,
+            (false, false) => Unateness::Binate,
+        }
+    }
+
+    /// whether the function is unchanged by exchanging inputs `i` and `j`
+    pub fn symmetric(&self, i: usize, j: usize) -> bool {
+        let (a, b) = (self.cofactor(i, false), self.cofactor(i, true));
+        a.cofactor(j, true) == b.cofactor(j, false)
+    }
+
+    /// whether the function is unchanged by exchanging inputs `i` and `j`
+    /// and negating both
+    pub fn antisymmetric(&self, i: usize, j: usize) -> bool {
+        let (a, b) = (self.cofactor(i, false), self.cofactor(i, true));
+        a.cofactor(j, false) == b.cofactor(j, true)
+    }
+
+    /// the function with input `var` negated
+    pub fn flip(&self, var: usize) -> Self {
+        assert!(var < self.vars);
+        let mut t = self.clone();
+        if var < 6 {
+            let s = 1 << var;
+            for w in &mut t.words {
+                *w = ((*w & VARS[var]) >> s) | ((*w & !VARS[var]) << s);
+            }
+        } else {
+            let stride = 1 << (var - 6);
+            for i in 0..t.words.len() {
+                t.words[i] = self.words[i ^ stride];
+            }
+        }
+        t
+    }
+
+    /// the function with inputs `i` and `j` exchanged
+    pub fn swap(&self, i: usize, j: usize) -> Self {
+        assert!(i < self.vars && j < self.vars);
+        let (i, j) = (i.min(j), i.max(j));
+        let mut t = self.clone();
+        if i == j {
+            return t;
+        }
+        if j < 6 {
+            // move the minterms with i set and j clear onto those with j set
+            // and i clear and back
+            let delta = (1 << j) - (1 << i);
+            let mask = VARS[i] & !VARS[j];
+            for w in &mut t.words {
+                *w = (*w & !(mask | (mask << delta)))
+                    | ((*w & mask) << delta)
+                    | ((*w >> delta) & mask);
+            }
+        } else if i < 6 {
+            let (s, stride) = (1 << i, 1 << (j - 6));
+            for k in (0..t.words.len()).filter(|k| k & stride == 0) {
+                let (a, b) = (self.words[k], self.words[k | stride]);
+                t.words[k] = (a & !VARS[i]) | ((b & !VARS[i]) << s);
+                t.words[k | stride] = (b & VARS[i]) | ((a & VARS[i]) >> s);
+            }
+        } else {
+            let (si, sj) = (1 << (i - 6), 1 << (j - 6));
+            for k in (0..t.words.len()).filter(|k| k & si != 0 && k & sj == 0) {
+                t.words.swap(k, k ^ si ^ sj);
+            }
+        }
+        t
+    }
+
+    /// the function with input `perm[i]` moved to input `i`
+    pub fn permute(&self, perm: &[usize]) -> Self {
+        assert_eq!(perm.len(), self.vars);
+        let mut t = self.clone();
+        // input of `self` currently at every position
+        let mut at: Vec<usize> = (0..self.vars).collect();
+        for (i, v) in perm.iter().enumerate() {
+            let p = at.iter().position(|x| x == v).unwrap();
+            if p != i {
+                t = t.swap(i, p);
+                at.swap(i, p);
+            }
+        }
+        t
+    }
+
+    pub fn apply(&self, npn: &Npn) -> Self {
+        let t = if npn.output {
+            !self.clone()
+        } else {
+            self.clone()
+        };
+        let t = (0..self.vars)
+            .filter(|v| npn.phases[*v])
+            .fold(t, |t, v| t.flip(v));
+        t.permute(&npn.perm)
+    }
+
+    /// Orders of the inputs by decreasing number of minterms of their
+    /// positive cofactors, trying every order of inputs with equal counts
+    /// except among symmetric ones
+    fn orders(&self) -> Vec<Vec<usize>> {
+        fn arrangements(
+            counts: &mut [usize],
+            current: &mut Vec<usize>,
+            len: usize,
+            out: &mut Vec<Vec<usize>>,
+        ) {
+            if current.len() == len {
+                out.push(current.clone());
+                return;
+            }
+            for c in 0..counts.len() {
+                if counts[c] > 0 {
+                    counts[c] -= 1;
+                    current.push(c);
+                    arrangements(counts, current, len, out);
+                    current.pop();
+                    counts[c] += 1;
+                }
+            }
+        }
+
+        let sig: Vec<usize> = (0..self.vars)
+            .map(|v| self.cofactor(v, true).ones())
+            .collect();
+        let mut vars: Vec<usize> = (0..self.vars).collect();
+        vars.sort_by_key(|v| (Reverse(sig[*v]), *v));
+        let classes = self.symmetry_classes();
+        let mut orders = vec![vec![]];
+        for group in vars.chunk_by(|a, b| sig[*a] == sig[*b]) {
+            // members of the group by symmetry class
+            let mut members: Vec<Vec<usize>> = vec![];
+            for c in &classes {
+                let m: Vec<usize> = c.iter().filter(|v| group.contains(v)).copied().collect();
+                if !m.is_empty() {
+                    members.push(m);
+                }
+            }
+            let mut labels = vec![];
+            let mut counts: Vec<usize> = members.iter().map(Vec::len).collect();
+            arrangements(&mut counts, &mut vec![], group.len(), &mut labels);
+            let mut next_orders = vec![];
+            for prefix in &orders {
+                for l in &labels {
+                    let mut next = vec![0; members.len()];
+                    let mut order = prefix.clone();
+                    for c in l {
+                        order.push(members[*c][next[*c]]);
+                        next[*c] += 1;
+                    }
+                    next_orders.push(order);
+                }
+            }
+            orders = next_orders;
+        }
+        orders
+    }
+
+    /// NPN canonical form, the smallest table among the NPN transforms of
+    /// the function, and the transform that gives it
+    ///
+    /// Only transforms that agree with the minterm counts of the function
+    /// and its cofactors are tried, which is practical up to `NPN_VARS` inputs.
+    pub fn npn(&self) -> (Self, Npn) {
+        let n = self.vars;
+        let ones = 2 * self.ones();
+        let outputs: &[bool] = match ones.cmp(&(1 << n)) {
+            std::cmp::Ordering::Less => &[false],
+            std::cmp::Ordering::Greater => &[true],
+            std::cmp::Ordering::Equal => &[false, true],
+        };
+        let mut best: Option<(Self, Npn)> = None;
+        for output in outputs {
+            let g = if *output { !self.clone() } else { self.clone() };
+            // negate inputs so that positive cofactors have more minterms
+            let mut phases = vec![false; n];
+            let mut free = vec![];
+            for (v, phase) in phases.iter_mut().enumerate() {
+                let (c0, c1) = (g.cofactor(v, false).ones(), g.cofactor(v, true).ones());
+                match c1.cmp(&c0) {
+                    std::cmp::Ordering::Less => *phase = true,
+                    std::cmp::Ordering::Equal => free.push(v),
+                    std::cmp::Ordering::Greater => (),
+                }
+            }
+            for mask in 0..1_usize << free.len() {
+                let mut phases = phases.clone();
+                for (k, v) in free.iter().enumerate() {
+                    phases[*v] = (mask >> k) & 1 == 1;
+                }
+                let h = (0..n)
+                    .filter(|v| phases[*v])
+                    .fold(g.clone(), |t, v| t.flip(v));
+                for perm in h.orders() {
+                    let t = h.permute(&perm);
+                    if best.as_ref().is_none_or(|(b, _)| t < *b) {
+                        let npn = Npn {
+                            output: *output,
+                            phases: phases.clone(),
+                            perm,
+                        };
+                        best = Some((t, npn));
+                    }
+                }
+            }
+        }
+        best.unwrap()
+    }
+
+    /// Classes of pairwise symmetric inputs, each sorted and ordered by its
+    /// first input
+    pub fn symmetry_classes(&self) -> Vec<Vec<usize>> {
+        let mut classes: Vec<Vec<usize>> = vec![];
+        for i in 0..self.vars {
+            // symmetry is transitive, comparing to one member is enough
+            match classes.iter_mut().find(|c| self.symmetric(c[0], i)) {
+                Some(c) => c.push(i),
+                None => classes.push(vec![i]),
+            }
+        }
+        classes
+    }
+}
+
+impl Not for Truth {
+    type Output = Self;
+
+    fn not(mut self) -> Self {
+        for w in &mut self.words {
+            *w = !*w;
+        }
+        self
+    }
+}
+
+impl Display for Truth {
+    /// hexadecimal, the last minterm first
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.vars < 6 {
+            let digits = (1_usize << self.vars).div_ceil(4);
+            let mask = if self.vars == 0 {
+                1
+            } else {
+                u64::MAX >> (64 - (1 << self.vars))
+            };
+            write!(f, "{:0width$x}", self.words[0] & mask, width = digits)
+        } else {
+            for w in self.words.iter().rev() {
+                write!(f, "{:016x}", w)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Npn, Truth, Unateness};
+    use crate::qmc::{Imp, Tri};
+
+    #[test]
+    fn basic() {
+        let maj = Truth::new(3, |x| (x[0] as u8 + x[1] as u8 + x[2] as u8) >= 2);
+        assert_eq!(maj.to_string(), "e8");
+        assert_eq!(maj.ones(), 4);
+        let a_and_b = maj.cofactor(2, false);
+        assert_eq!(a_and_b, Truth::new(3, |x| x[0] && x[1]));
+        let imps = [
+            Imp(vec![Tri::T, Tri::T, Tri::X]),
+            Imp(vec![Tri::T, Tri::X, Tri::T]),
+            Imp(vec![Tri::X, Tri::T, Tri::T]),
+        ];
+        assert_eq!(Truth::from_imps(3, &imps), maj);
+
+        let wide = Truth::new(8, |x| x[7] && !x[0]);
+        assert_eq!(wide.ones(), 64);
+        assert_eq!(wide.cofactor(7, true), Truth::new(8, |x| !x[0]));
+        assert_eq!(wide.cofactor(0, false), Truth::new(8, |x| x[7]));
+        assert_eq!(Truth::new(0, |_| true).to_string(), "1");
+    }
+
+    #[test]
+    fn symmetry() {
+        // a & b | c ^ d
+        let f = Truth::new(4, |x| (x[0] && x[1]) || (x[2] ^ x[3]));
+        assert!(f.symmetric(0, 1));
+        assert!(f.symmetric(2, 3));
+        assert!(!f.symmetric(1, 2));
+        assert!(f.antisymmetric(2, 3));
+        assert!(!f.antisymmetric(0, 1));
+        assert_eq!(f.symmetry_classes(), [vec![0, 1], vec![2, 3]]);
+        assert!(f.depends(3));
+        assert!(!f.cofactor(3, true).depends(3));
+
+        // a & !b is symmetric in a and !b
+        let g = Truth::new(2, |x| x[0] && !x[1]);
+        assert!(!g.symmetric(0, 1));
+        assert!(g.antisymmetric(0, 1));
+    }
+
+    #[test]
+    fn unate() {
+        // a & !b | c ^ d, e unused
+        let f = Truth::new(5, |x| (x[0] && !x[1]) || (x[2] ^ x[3]));
+        let u: Vec<_> = (0..5).map(|v| f.unateness(v)).collect();
+        assert_eq!(
+            u,
+            [
+                Unateness::Positive,
+                Unateness::Negative,
+                Unateness::Binate,
+                Unateness::Binate,
+                Unateness::Independent
+            ]
+        );
+        assert_eq!(u[1].to_string(), "negative unate");
+    }
+
+    #[test]
+    fn transforms() {
+        for vars in [4, 7, 8] {
+            let f = Truth::new(vars, |x| (x[0] && !x[1]) || (x[2] ^ x[vars - 1]));
+            assert_eq!(f.swap(0, vars - 1).swap(0, vars - 1), f);
+            assert_eq!(f.flip(vars - 1).flip(vars - 1), f);
+            let g = f.swap(1, vars - 1);
+            let expected = Truth::new(vars, |x| (x[0] && !x[vars - 1]) || (x[2] ^ x[1]));
+            assert_eq!(g, expected);
+            let h = f.flip(1).flip(0).swap(0, 2);
+            let expected = Truth::new(vars, |x| (!x[2] && x[1]) || (x[0] ^ x[vars - 1]));
+            assert_eq!(h, expected);
+        }
+    }
+
+    #[test]
+    fn npn() {
+        // every NPN transform of a function has the same canonical form
+        let f = Truth::new(4, |x| (x[0] && !x[1]) || (x[2] && x[3]));
+        let (canonical, npn) = f.npn();
+        assert_eq!(f.apply(&npn), canonical);
+        for (output, perm) in [
+            (false, [1, 0, 2, 3]),
+            (true, [3, 2, 0, 1]),
+            (true, [2, 3, 1, 0]),
+        ] {
+            let t = Npn {
+                output,
+                phases: vec![true, false, false, true],
+                perm: perm.to_vec(),
+            };
+            let g = f.apply(&t);
+            let (c, n) = g.npn();
+            assert_eq!(c, canonical);
+            assert_eq!(g.apply(&n), c);
+        }
+        let other = Truth::new(4, |x| (x[0] || x[1]) && (x[2] ^ x[3]));
+        assert_ne!(other.npn().0, canonical);
+
+        // symmetric functions of 8 inputs stay fast
+        let xor = Truth::new(8, |x| x.iter().filter(|v| **v).count() % 2 == 1);
+        let (c, n) = xor.npn();
+        assert_eq!(xor.apply(&n), c);
+    }
+}
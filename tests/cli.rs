@@ -0,0 +1,87 @@
+use std::process::Command;
+
+/// Run the binary on a design written to a file named `name`, whose
+/// extension gives the format, returning its standard output or, when it
+/// fails, its standard error
+fn try_run(name: &str, design: &str, args: &[&str]) -> Result<String, String> {
+    let file = std::env::temp_dir().join(format!("rbc-{}-{}", std::process::id(), name));
+    std::fs::write(&file, design).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rbc"))
+        .args(args)
+        .arg(&file)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&file).unwrap();
+    if output.status.success() {
+        Ok(String::from_utf8(output.stdout).unwrap())
+    } else {
+        Err(String::from_utf8(output.stderr).unwrap())
+    }
+}
+
+fn run(name: &str, design: &str, args: &[&str]) -> String {
+    try_run(name, design, args).unwrap()
+}
+
+#[test]
+fn chart_over_support() {
+    // B is an input of the design that the output does not depend on
+    let expression = "(((A&C)|(!C))|(B&(!B)))";
+    let text = run("text.exp", expression, &["-c", "7"]);
+    assert!(text.contains("over inputs 0 2\n   00 10 11\n"));
+    assert!(!text.contains("--"));
+
+    let csv = run("csv.exp", expression, &["-c", "8", "--format", "csv"]);
+    assert_eq!(csv, "prime over inputs 0 2,00,10,11\n1-,0,1,1\n-0,1,1,0\n");
+
+    let json = run("json.exp", expression, &["-c", "8", "--format", "json"]);
+    assert_eq!(
+        json,
+        "{\"output\":0,\"essentials\":[\"1-\",\"-0\"],\"chart\":{\"inputs\":[0,2],\
+         \"primes\":[\"1-\",\"-0\"],\"minterms\":[\"00\",\"10\",\"11\"],\
+         \"marks\":[[false,true,true],[true,true,false]]}}\n"
+    );
+}
+
+#[test]
+fn npn_width() {
+    let wide = "((((A&B)&(C&D))&((E&F)&(G&H)))&I)";
+    let e = try_run("npn.exp", wide, &["-c", "27"]).unwrap_err();
+    assert!(e.contains("output 0 depends on 9 inputs, NPN classes need at most 8"));
+    assert!(run("npn8.exp", "(((A&B)&(C&D))&((E&F)&(G&H)))", &["-c", "27"]).contains("NPN class"));
+}
+
+#[test]
+fn verify_over_support() {
+    // 24 inputs of which the output only depends on A and B
+    let expression = "((A&B)|(X&(!X)))";
+    let sop = run("sop.exp", expression, &["-c", "5", "--verify=true"]);
+    assert!(sop.starts_with("minimized SOP of output 0: 11----------------------, saved"));
+    let pos = run("pos.exp", expression, &["-c", "6", "--verify=true"]);
+    assert!(pos.starts_with("minimized POS of output 0: (1 + - + - + "));
+    let factored = run("factored.exp", expression, &["-c", "21", "--verify=true"]);
+    assert!(factored.starts_with("factored form of output 0: A B,"));
+}
+
+#[test]
+fn verify_flag() {
+    // a bare --verify leaves the design file to the positional arguments
+    let sop = run("flag.exp", "(A|B)", &["-c", "5", "--verify"]);
+    assert!(sop.starts_with("minimized SOP of output 0: 1- + -1,"));
+    assert!(try_run("value.exp", "(A|B)", &["-c", "5", "--verify", "false"]).is_err());
+}
+
+#[test]
+fn wide_support() {
+    // i0 & i1 out of 70 inputs, 2^68 canonical minterms outside the support
+    let mut aag = "aag 71 70 0 1 1\n".to_string();
+    for i in 1..=70 {
+        aag += &format!("{}\n", 2 * i);
+    }
+    aag += "142\n142 2 4\n";
+    let sop = run("wide.aag", &aag, &["-c", "5", "--verify=true"]);
+    assert!(sop.contains(": 11------"));
+    assert!(sop.contains(", saved 0 literals over the support, 2 literals factored"));
+    let pos = run("wide.aag", &aag, &["-c", "6", "--verify=true"]);
+    assert!(pos.contains(", saved 4 literals over the support, 2 literals factored"));
+}